@@ -45,6 +45,26 @@
 //!
 //!   Note: WebRender has a reduced fork of this crate, so that we can avoid
 //!   publishing this crate on crates.io.
+//!
+//! Without a real allocator able to report the size of a heap block (e.g. when building for
+//! wasm or another embedded target with no `malloc_usable_size`-equivalent), construct a
+//! [`MallocSizeOfOps`] with [`MallocSizeOfOps::new_estimate`] instead of [`MallocSizeOfOps::new`].
+//! Measurements then fall back to `capacity() * size_of::<T>()`-style estimates rather than
+//! asking the allocator, the same way they already do when only `enclosing_size_of_op` is
+//! missing.
+//!
+//! The `std` feature is on by default; building with `default-features = false` switches the
+//! crate to `#![no_std]` plus `alloc`, for embedders (e.g. allocator-instrumented `no_std`
+//! targets) that want the trait set without pulling in `std` itself. The impls for
+//! `HashMap`/`HashSet`, `Mutex`/`RwLock`, `std::sync::Arc` and
+//! `VecDeque`/`BinaryHeap`/`LinkedList`/`BTreeSet` are gated out in that configuration,
+//! including those whose types `alloc` also provides; everything else compiles unchanged
+//! either way.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
 
 extern crate app_units;
 extern crate cssparser;
@@ -55,11 +75,51 @@ extern crate smallbitvec;
 extern crate smallvec;
 extern crate void;
 
-use std::hash::{BuildHasher, Hash};
-use std::mem::size_of;
-use std::ops::Range;
-use std::ops::{Deref, DerefMut};
-use std::os::raw::c_void;
+#[cfg(feature = "crossbeam-channel")]
+extern crate crossbeam_channel;
+#[cfg(feature = "parking_lot")]
+extern crate parking_lot;
+#[cfg(feature = "serde_bytes")]
+extern crate serde_bytes;
+#[cfg(feature = "servo")]
+extern crate string_cache;
+#[cfg(feature = "time")]
+extern crate time;
+#[cfg(feature = "url")]
+extern crate url;
+#[cfg(feature = "uuid")]
+extern crate uuid;
+
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+#[cfg(feature = "std")]
+use std::boxed::Box;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::string::String;
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+#[cfg(not(feature = "std"))]
+use alloc::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use alloc::borrow::ToOwned;
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use core::hash::{BuildHasher, Hash};
+use core::mem::size_of;
+use core::mem::size_of_val;
+use core::ops::{Range, RangeInclusive};
+use core::ops::{Deref, DerefMut};
+use core::ffi::c_void;
 use void::Void;
 
 /// A C function that takes a pointer to a heap allocation and returns its size.
@@ -70,8 +130,12 @@ type VoidPtrToBoolFnMut = dyn FnMut(*const c_void) -> bool;
 
 /// Operations used when measuring heap usage of data structures.
 pub struct MallocSizeOfOps {
-    /// A function that returns the size of a heap allocation.
-    size_of_op: VoidPtrToSizeFn,
+    /// A function that returns the size of a heap allocation. Optional because not every
+    /// target has a real allocator that can report this (e.g. wasm, or other embedded targets
+    /// without a `malloc_usable_size`-equivalent). If it's not provided, measurements fall
+    /// back to `capacity() * size_of::<T>()`-style estimates, the same as when only
+    /// `enclosing_size_of_op` is missing.
+    size_of_op: Option<VoidPtrToSizeFn>,
 
     /// Like `size_of_op`, but can take an interior pointer. Optional because
     /// not all allocators support this operation. If it's not provided, some
@@ -79,6 +143,12 @@ pub struct MallocSizeOfOps {
     /// real and accurate measurements.
     enclosing_size_of_op: Option<VoidPtrToSizeFn>,
 
+    /// The number of real heap blocks measured so far via `size_of_op`/`enclosing_size_of_op`
+    /// (i.e. excluding the small-pointer guard and estimate fallbacks). about:memory wants both
+    /// total bytes and the number of distinct allocations backing them, so this is counted here
+    /// rather than in each collection/Component impl.
+    blocks_counted: core::cell::Cell<usize>,
+
     /// Check if a pointer has been seen before, and remember it for next time.
     /// Useful when measuring `Rc`s and `Arc`s. Optional, because many places
     /// don't need it.
@@ -92,17 +162,54 @@ impl MallocSizeOfOps {
         have_seen_ptr: Option<Box<VoidPtrToBoolFnMut>>,
     ) -> Self {
         MallocSizeOfOps {
-            size_of_op: size_of,
+            size_of_op: Some(size_of),
             enclosing_size_of_op: malloc_enclosing_size_of,
+            blocks_counted: core::cell::Cell::new(0),
             have_seen_ptr_op: have_seen_ptr,
         }
     }
 
+    /// Build an `ops` with no real allocator introspection at all, for targets that can't
+    /// provide one. All measurements fall back to capacity-based estimates.
+    pub fn new_estimate() -> Self {
+        MallocSizeOfOps {
+            size_of_op: None,
+            enclosing_size_of_op: None,
+            blocks_counted: core::cell::Cell::new(0),
+            have_seen_ptr_op: None,
+        }
+    }
+
+    /// Equivalent to `new_estimate()`, gated behind the `estimate-heapsize` feature for
+    /// embedders (e.g. wasm with no `malloc_usable_size`-equivalent) that want measurement to
+    /// compile in purely-estimated mode without depending on Firefox/mozjemalloc at all.
+    #[cfg(feature = "estimate-heapsize")]
+    pub fn new_estimating() -> Self {
+        Self::new_estimate()
+    }
+
+    /// Build an `ops` wired to the `usable_size` query of the global allocator backend
+    /// compiled in via the `usable-size-jemalloc` feature, so non-Gecko embedders can measure
+    /// with `value.size_of(&mut ops)` without hand-writing the unsafe `VoidPtrToSizeFn`
+    /// themselves. Falls back to `new_estimate()` when that backend isn't enabled: jemalloc is
+    /// the only allocator here whose public API actually exposes a `usable_size`-style query.
+    #[cfg(feature = "usable-size-jemalloc")]
+    pub fn from_global_allocator() -> Self {
+        Self::new(allocator_usable_size, Some(allocator_usable_size), None)
+    }
+
+    /// See the other `from_global_allocator()`; this is the fallback used when no
+    /// `usable-size-*` backend feature is enabled.
+    #[cfg(not(feature = "usable-size-jemalloc"))]
+    pub fn from_global_allocator() -> Self {
+        Self::new_estimate()
+    }
+
     /// Check if an allocation is empty. This relies on knowledge of how Rust
     /// handles empty allocations, which may change in the future.
     fn is_empty<T: ?Sized>(ptr: *const T) -> bool {
         // The correct condition is this:
-        //   `ptr as usize <= ::std::mem::align_of::<T>()`
+        //   `ptr as usize <= ::core::mem::align_of::<T>()`
         // But we can't call align_of() on a ?Sized T. So we approximate it
         // with the following. 256 is large enough that it should always be
         // larger than the required alignment, but small enough that it is
@@ -111,13 +218,19 @@ impl MallocSizeOfOps {
         return ptr as *const usize as usize <= 256;
     }
 
-    /// Call `size_of_op` on `ptr`, first checking that the allocation isn't
-    /// empty, because some types (such as `Vec`) utilize empty allocations.
+    /// Is a `size_of_op` available?
+    pub fn has_malloc_size_of(&self) -> bool {
+        self.size_of_op.is_some()
+    }
+
+    /// Call `size_of_op`, which must be available, on `ptr`, first checking that the
+    /// allocation isn't empty, because some types (such as `Vec`) utilize empty allocations.
     pub unsafe fn malloc_size_of<T: ?Sized>(&self, ptr: *const T) -> usize {
         if MallocSizeOfOps::is_empty(ptr) {
             0
         } else {
-            (self.size_of_op)(ptr as *const c_void)
+            self.blocks_counted.set(self.blocks_counted.get() + 1);
+            (self.size_of_op.expect("missing size_of_op"))(ptr as *const c_void)
         }
     }
 
@@ -126,10 +239,18 @@ impl MallocSizeOfOps {
         self.enclosing_size_of_op.is_some()
     }
 
+    /// The number of real heap blocks measured so far via `malloc_size_of`/
+    /// `malloc_enclosing_size_of` (the small-pointer guard and estimate fallbacks don't count,
+    /// since they don't correspond to an actual measured allocation).
+    pub fn blocks_counted(&self) -> usize {
+        self.blocks_counted.get()
+    }
+
     /// Call `enclosing_size_of_op`, which must be available, on `ptr`, which
     /// must not be empty.
     pub unsafe fn malloc_enclosing_size_of<T>(&self, ptr: *const T) -> usize {
         assert!(!MallocSizeOfOps::is_empty(ptr));
+        self.blocks_counted.set(self.blocks_counted.get() + 1);
         (self.enclosing_size_of_op.unwrap())(ptr as *const c_void)
     }
 
@@ -141,6 +262,27 @@ impl MallocSizeOfOps {
             .expect("missing have_seen_ptr_op");
         have_seen_ptr_op(ptr as *const c_void)
     }
+
+    /// Measure `ptr` via `size_of_op` if one is available, otherwise return `estimate`. This
+    /// centralizes the has_malloc_size_of()/estimate branch that each shallow-size impl would
+    /// otherwise have to repeat, so callers in `estimate-heapsize` mode (no allocator
+    /// introspection at all) fall back uniformly.
+    pub unsafe fn malloc_size_of_or_estimate<T: ?Sized>(&self, ptr: *const T, estimate: usize) -> usize {
+        if self.has_malloc_size_of() {
+            self.malloc_size_of(ptr)
+        } else {
+            estimate
+        }
+    }
+
+    /// Like `malloc_size_of_or_estimate`, but for interior pointers via `enclosing_size_of_op`.
+    pub unsafe fn malloc_enclosing_size_of_or_estimate<T>(&self, ptr: *const T, estimate: usize) -> usize {
+        if self.has_malloc_enclosing_size_of() {
+            self.malloc_enclosing_size_of(ptr)
+        } else {
+            estimate
+        }
+    }
 }
 
 /// Trait for measuring the "deep" heap usage of a data structure. This is the
@@ -193,7 +335,7 @@ pub trait MallocConditionalShallowSizeOf {
 
 impl MallocSizeOf for String {
     fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
-        unsafe { ops.malloc_size_of(self.as_ptr()) }
+        unsafe { ops.malloc_size_of_or_estimate(self.as_ptr(), self.capacity()) }
     }
 }
 
@@ -206,7 +348,11 @@ impl<'a, T: ?Sized> MallocSizeOf for &'a T {
 
 impl<T: ?Sized> MallocShallowSizeOf for Box<T> {
     fn shallow_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
-        unsafe { ops.malloc_size_of(&**self) }
+        // `size_of_val` reports the pointee's in-memory size for any `T: ?Sized` (the
+        // element count times element size for a `[T]`, the concrete size behind a `dyn
+        // Trait` vtable, etc.), so it doubles as a correct `estimate-heapsize` fallback
+        // without needing a `Box<[T]>`-specific impl that would conflict with this one.
+        unsafe { ops.malloc_size_of_or_estimate(&**self, size_of_val(&**self)) }
     }
 }
 
@@ -274,26 +420,26 @@ impl<T: MallocSizeOf, E: MallocSizeOf> MallocSizeOf for Result<T, E> {
     }
 }
 
-impl<T: MallocSizeOf + Copy> MallocSizeOf for std::cell::Cell<T> {
+impl<T: MallocSizeOf + Copy> MallocSizeOf for core::cell::Cell<T> {
     fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
         self.get().size_of(ops)
     }
 }
 
-impl<T: MallocSizeOf> MallocSizeOf for std::cell::RefCell<T> {
+impl<T: MallocSizeOf> MallocSizeOf for core::cell::RefCell<T> {
     fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
         self.borrow().size_of(ops)
     }
 }
 
-impl<'a, B: ?Sized + ToOwned> MallocSizeOf for std::borrow::Cow<'a, B>
+impl<'a, B: ?Sized + ToOwned> MallocSizeOf for Cow<'a, B>
 where
     B::Owned: MallocSizeOf,
 {
     fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
         match *self {
-            std::borrow::Cow::Borrowed(_) => 0,
-            std::borrow::Cow::Owned(ref b) => b.size_of(ops),
+            Cow::Borrowed(_) => 0,
+            Cow::Owned(ref b) => b.size_of(ops),
         }
     }
 }
@@ -310,7 +456,7 @@ impl<T: MallocSizeOf> MallocSizeOf for [T] {
 
 impl<T> MallocShallowSizeOf for Vec<T> {
     fn shallow_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
-        unsafe { ops.malloc_size_of(self.as_ptr()) }
+        unsafe { ops.malloc_size_of_or_estimate(self.as_ptr(), self.capacity() * size_of::<T>()) }
     }
 }
 
@@ -324,16 +470,38 @@ impl<T: MallocSizeOf> MallocSizeOf for Vec<T> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<T> MallocShallowSizeOf for std::collections::VecDeque<T> {
+    fn shallow_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        let estimate = self.capacity() * size_of::<T>();
+        match self.front() {
+            // The front element is an interior pointer.
+            Some(front) => unsafe { ops.malloc_enclosing_size_of_or_estimate(front, estimate) },
+            // This assumes that no memory is allocated when the VecDeque is empty.
+            None if ops.has_malloc_enclosing_size_of() => 0,
+            None => estimate,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: MallocSizeOf> MallocSizeOf for std::collections::VecDeque<T> {
+    fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        let mut n = self.shallow_size_of(ops);
+        for elem in self.iter() {
+            n += elem.size_of(ops);
+        }
+        n
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> MallocShallowSizeOf for std::collections::BinaryHeap<T> {
     fn shallow_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
         if ops.has_malloc_enclosing_size_of() {
-            if let Some(front) = self.front() {
-                // The front element is an interior pointer.
-                unsafe { ops.malloc_enclosing_size_of(&*front) }
-            } else {
-                // This assumes that no memory is allocated when the VecDeque is empty.
-                0
-            }
+            self.iter()
+                .next()
+                .map_or(0, |elem| unsafe { ops.malloc_enclosing_size_of(elem) })
         } else {
             // An estimate.
             self.capacity() * size_of::<T>()
@@ -341,7 +509,8 @@ impl<T> MallocShallowSizeOf for std::collections::VecDeque<T> {
     }
 }
 
-impl<T: MallocSizeOf> MallocSizeOf for std::collections::VecDeque<T> {
+#[cfg(feature = "std")]
+impl<T: MallocSizeOf + Ord> MallocSizeOf for std::collections::BinaryHeap<T> {
     fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
         let mut n = self.shallow_size_of(ops);
         for elem in self.iter() {
@@ -351,12 +520,40 @@ impl<T: MallocSizeOf> MallocSizeOf for std::collections::VecDeque<T> {
     }
 }
 
-impl<A: smallvec::Array> MallocShallowSizeOf for smallvec::SmallVec<A> {
+#[cfg(feature = "std")]
+impl<T> MallocShallowSizeOf for std::collections::LinkedList<T> {
     fn shallow_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
-        if self.spilled() {
-            unsafe { ops.malloc_size_of(self.as_ptr()) }
+        if ops.has_malloc_enclosing_size_of() {
+            // Unlike VecDeque's single contiguous buffer, each element here lives in its own
+            // node allocation, so every node has to be visited individually.
+            self.iter()
+                .map(|elem| unsafe { ops.malloc_enclosing_size_of(elem) })
+                .sum()
         } else {
+            // An estimate: each node is a separate allocation holding one T.
+            self.len() * size_of::<T>()
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: MallocSizeOf> MallocSizeOf for std::collections::LinkedList<T> {
+    fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        let mut n = self.shallow_size_of(ops);
+        for elem in self.iter() {
+            n += elem.size_of(ops);
+        }
+        n
+    }
+}
+
+impl<A: smallvec::Array> MallocShallowSizeOf for smallvec::SmallVec<A> {
+    fn shallow_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        if !self.spilled() {
             0
+        } else {
+            let estimate = self.capacity() * size_of::<A::Item>();
+            unsafe { ops.malloc_size_of_or_estimate(self.as_ptr(), estimate) }
         }
     }
 }
@@ -382,11 +579,11 @@ impl<T> MallocShallowSizeOf for thin_vec::ThinVec<T> {
             return 0;
         }
 
-        assert_eq!(
-            std::mem::size_of::<Self>(),
-            std::mem::size_of::<*const ()>()
-        );
-        unsafe { ops.malloc_size_of(*(self as *const Self as *const *const ())) }
+        let estimate = self.capacity() * size_of::<T>();
+        assert_eq!(size_of::<Self>(), size_of::<*const ()>());
+        unsafe {
+            ops.malloc_size_of_or_estimate(*(self as *const Self as *const *const ()), estimate)
+        }
     }
 }
 
@@ -439,6 +636,7 @@ macro_rules! malloc_size_of_hash_set {
     };
 }
 
+#[cfg(feature = "std")]
 malloc_size_of_hash_set!(std::collections::HashSet<T, S>);
 
 macro_rules! malloc_size_of_hash_map {
@@ -478,9 +676,10 @@ macro_rules! malloc_size_of_hash_map {
     };
 }
 
+#[cfg(feature = "std")]
 malloc_size_of_hash_map!(std::collections::HashMap<K, V, S>);
 
-impl<K, V> MallocShallowSizeOf for std::collections::BTreeMap<K, V>
+impl<K, V> MallocShallowSizeOf for BTreeMap<K, V>
 where
     K: Eq + Hash,
 {
@@ -495,7 +694,7 @@ where
     }
 }
 
-impl<K, V> MallocSizeOf for std::collections::BTreeMap<K, V>
+impl<K, V> MallocSizeOf for BTreeMap<K, V>
 where
     K: Eq + Hash + MallocSizeOf,
     V: MallocSizeOf,
@@ -510,8 +709,38 @@ where
     }
 }
 
+#[cfg(feature = "std")]
+impl<T> MallocShallowSizeOf for std::collections::BTreeSet<T>
+where
+    T: Eq + Hash,
+{
+    fn shallow_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        if ops.has_malloc_enclosing_size_of() {
+            self.iter()
+                .next()
+                .map_or(0, |v| unsafe { ops.malloc_enclosing_size_of(v) })
+        } else {
+            self.len() * (size_of::<T>() + size_of::<usize>())
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> MallocSizeOf for std::collections::BTreeSet<T>
+where
+    T: Eq + Hash + MallocSizeOf,
+{
+    fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        let mut n = self.shallow_size_of(ops);
+        for v in self.iter() {
+            n += v.size_of(ops);
+        }
+        n
+    }
+}
+
 // PhantomData is always 0.
-impl<T> MallocSizeOf for std::marker::PhantomData<T> {
+impl<T> MallocSizeOf for core::marker::PhantomData<T> {
     fn size_of(&self, _ops: &mut MallocSizeOfOps) -> usize {
         0
     }
@@ -556,15 +785,95 @@ impl<T: MallocSizeOf> MallocConditionalSizeOf for servo_arc::Arc<T> {
     }
 }
 
+// Unlike servo_arc::Arc, std::sync::Arc doesn't expose a pointer to the start of its
+// allocation, only `as_ptr`'s interior pointer to the T field, so these go through
+// `malloc_enclosing_size_of` rather than `malloc_size_of`.
+#[cfg(feature = "std")]
+impl<T> MallocUnconditionalShallowSizeOf for std::sync::Arc<T> {
+    fn unconditional_shallow_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        // Estimate: the strong and weak counts followed by the T.
+        let estimate = 2 * size_of::<usize>() + size_of::<T>();
+        unsafe { ops.malloc_enclosing_size_of_or_estimate(std::sync::Arc::as_ptr(self), estimate) }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: MallocSizeOf> MallocUnconditionalSizeOf for std::sync::Arc<T> {
+    fn unconditional_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        self.unconditional_shallow_size_of(ops) + (**self).size_of(ops)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> MallocConditionalShallowSizeOf for std::sync::Arc<T> {
+    fn conditional_shallow_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        if ops.have_seen_ptr(std::sync::Arc::as_ptr(self)) {
+            0
+        } else {
+            self.unconditional_shallow_size_of(ops)
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: MallocSizeOf> MallocConditionalSizeOf for std::sync::Arc<T> {
+    fn conditional_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        if ops.have_seen_ptr(std::sync::Arc::as_ptr(self)) {
+            0
+        } else {
+            self.unconditional_size_of(ops)
+        }
+    }
+}
+
 /// If a mutex is stored directly as a member of a data type that is being measured,
 /// it is the unique owner of its contents and deserves to be measured.
 ///
 /// If a mutex is stored inside of an Arc value as a member of a data type that is being measured,
 /// the Arc will not be automatically measured so there is no risk of overcounting the mutex's
 /// contents.
+///
+/// Measurement uses `try_lock` rather than `lock`: a whole-heap traversal may walk into a lock
+/// already held by the measuring thread (or one that's been poisoned), and blocking or panicking
+/// there would take the whole traversal down with it. When the lock can't be acquired, fall back
+/// to `T`'s stack size as an estimate of what it's holding.
+#[cfg(feature = "std")]
 impl<T: MallocSizeOf> MallocSizeOf for std::sync::Mutex<T> {
     fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
-        (*self.lock().unwrap()).size_of(ops)
+        match self.try_lock() {
+            Ok(val) => (*val).size_of(ops),
+            Err(_) => size_of::<T>(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: MallocSizeOf> MallocSizeOf for std::sync::RwLock<T> {
+    fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        match self.try_read() {
+            Ok(val) => (*val).size_of(ops),
+            Err(_) => size_of::<T>(),
+        }
+    }
+}
+
+#[cfg(feature = "parking_lot")]
+impl<T: MallocSizeOf> MallocSizeOf for parking_lot::Mutex<T> {
+    fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        match self.try_lock() {
+            Some(val) => (*val).size_of(ops),
+            None => size_of::<T>(),
+        }
+    }
+}
+
+#[cfg(feature = "parking_lot")]
+impl<T: MallocSizeOf> MallocSizeOf for parking_lot::RwLock<T> {
+    fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        match self.try_read() {
+            Some(val) => (*val).size_of(ops),
+            None => size_of::<T>(),
+        }
     }
 }
 
@@ -683,6 +992,25 @@ where
     }
 }
 
+// A nested selector list (e.g. inside `:is()`/`:where()`/`:host()`) isn't necessarily this
+// selector's own primary reference the way the top-level `Selector` is: its ThinArc can be
+// reached through more than one enclosing selector, so it has to be measured conditionally
+// rather than unconditionally to avoid counting the same heap block once per path.
+impl<Impl: selectors::parser::SelectorImpl> MallocConditionalSizeOf
+    for selectors::parser::Selector<Impl>
+where
+    Impl::NonTSPseudoClass: MallocSizeOf,
+    Impl::PseudoElement: MallocSizeOf,
+{
+    fn conditional_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        if ops.have_seen_ptr(self.thin_arc_heap_ptr()) {
+            0
+        } else {
+            self.unconditional_size_of(ops)
+        }
+    }
+}
+
 impl<Impl: selectors::parser::SelectorImpl> MallocUnconditionalSizeOf
     for selectors::parser::SelectorList<Impl>
 where
@@ -704,6 +1032,21 @@ where
     }
 }
 
+impl<Impl: selectors::parser::SelectorImpl> MallocConditionalSizeOf
+    for selectors::parser::SelectorList<Impl>
+where
+    Impl::NonTSPseudoClass: MallocSizeOf,
+    Impl::PseudoElement: MallocSizeOf,
+{
+    fn conditional_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        if ops.have_seen_ptr(self.thin_arc_heap_ptr()) {
+            0
+        } else {
+            self.unconditional_size_of(ops)
+        }
+    }
+}
+
 impl<Impl: selectors::parser::SelectorImpl> MallocUnconditionalSizeOf
     for selectors::parser::Component<Impl>
 where
@@ -715,12 +1058,15 @@ where
 
         match self {
             Component::AttributeOther(ref attr_selector) => attr_selector.size_of(ops),
-            Component::Negation(ref components) => components.unconditional_size_of(ops),
+            // These are nested ThinArc-backed selector lists that can be reached through more
+            // than one enclosing selector, so they're measured conditionally rather than
+            // unconditionally to avoid double-counting a shared allocation.
+            Component::Negation(ref components) => components.conditional_size_of(ops),
             Component::NonTSPseudoClass(ref pseudo) => (*pseudo).size_of(ops),
             Component::Slotted(ref selector) | Component::Host(Some(ref selector)) => {
-                selector.unconditional_size_of(ops)
+                selector.conditional_size_of(ops)
             },
-            Component::Is(ref list) | Component::Where(ref list) => list.unconditional_size_of(ops),
+            Component::Is(ref list) | Component::Where(ref list) => list.conditional_size_of(ops),
             Component::Has(ref relative_selectors) => relative_selectors.size_of(ops),
             Component::NthOf(ref nth_of_data) => nth_of_data.size_of(ops),
             Component::PseudoElement(ref pseudo) => (*pseudo).size_of(ops),
@@ -772,6 +1118,48 @@ impl MallocSizeOf for Void {
 
 #[cfg(feature = "servo")]
 impl<Static: string_cache::StaticAtomSet> MallocSizeOf for string_cache::Atom<Static> {
+    fn size_of(&self, _ops: &mut MallocSizeOfOps) -> usize {
+        // Atoms are interned and shared between every clone, so there's no single owner to
+        // charge the backing allocation to; reporting it here would multiply-count it once per
+        // clone in the graph. string_cache doesn't expose whether a given atom is static,
+        // inline, or heap-backed dynamic, so there's no way to even restrict this to the
+        // dynamic case without it.
+        0
+    }
+}
+
+#[cfg(feature = "url")]
+impl MallocSizeOf for url::Url {
+    fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        unsafe { ops.malloc_size_of_or_estimate(self.as_str().as_ptr(), self.as_str().len()) }
+    }
+}
+
+#[cfg(feature = "uuid")]
+malloc_size_of_is_0!(uuid::Uuid);
+
+#[cfg(feature = "time")]
+malloc_size_of_is_0!(time::Duration);
+
+#[cfg(feature = "serde_bytes")]
+impl MallocSizeOf for serde_bytes::ByteBuf {
+    fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        let bytes: &Vec<u8> = self;
+        bytes.size_of(ops)
+    }
+}
+
+#[cfg(feature = "crossbeam-channel")]
+impl<T> MallocSizeOf for crossbeam_channel::Sender<T> {
+    fn size_of(&self, _ops: &mut MallocSizeOfOps) -> usize {
+        // The channel's queue is shared by every Sender/Receiver clone; charging it here would
+        // multiply-count it once per handle, so (as with Atom above) it's left unmeasured.
+        0
+    }
+}
+
+#[cfg(feature = "crossbeam-channel")]
+impl<T> MallocSizeOf for crossbeam_channel::Receiver<T> {
     fn size_of(&self, _ops: &mut MallocSizeOfOps) -> usize {
         0
     }
@@ -807,22 +1195,76 @@ malloc_size_of_is_0!(u8, u16, u32, u64, u128, usize);
 malloc_size_of_is_0!(i8, i16, i32, i64, i128, isize);
 malloc_size_of_is_0!(f32, f64);
 
-malloc_size_of_is_0!(std::sync::atomic::AtomicBool);
-malloc_size_of_is_0!(std::sync::atomic::AtomicIsize);
-malloc_size_of_is_0!(std::sync::atomic::AtomicUsize);
-malloc_size_of_is_0!(std::num::NonZeroUsize);
-malloc_size_of_is_0!(std::num::NonZeroU64);
+malloc_size_of_is_0!(core::sync::atomic::AtomicBool);
+malloc_size_of_is_0!(core::sync::atomic::AtomicIsize);
+malloc_size_of_is_0!(core::sync::atomic::AtomicUsize);
+malloc_size_of_is_0!(core::sync::atomic::AtomicI8, core::sync::atomic::AtomicI16);
+malloc_size_of_is_0!(core::sync::atomic::AtomicI32, core::sync::atomic::AtomicI64);
+malloc_size_of_is_0!(core::sync::atomic::AtomicU8, core::sync::atomic::AtomicU16);
+malloc_size_of_is_0!(core::sync::atomic::AtomicU32, core::sync::atomic::AtomicU64);
+malloc_size_of_is_0!(core::num::NonZeroUsize);
+malloc_size_of_is_0!(core::num::NonZeroU64);
 
 malloc_size_of_is_0!(Range<u8>, Range<u16>, Range<u32>, Range<u64>, Range<usize>);
 malloc_size_of_is_0!(Range<i8>, Range<i16>, Range<i32>, Range<i64>, Range<isize>);
 malloc_size_of_is_0!(Range<f32>, Range<f64>);
 
+malloc_size_of_is_0!(RangeInclusive<u8>, RangeInclusive<u16>, RangeInclusive<u32>, RangeInclusive<u64>, RangeInclusive<usize>);
+malloc_size_of_is_0!(RangeInclusive<i8>, RangeInclusive<i16>, RangeInclusive<i32>, RangeInclusive<i64>, RangeInclusive<isize>);
+malloc_size_of_is_0!(RangeInclusive<f32>, RangeInclusive<f64>);
+
 malloc_size_of_is_0!(app_units::Au);
 
 malloc_size_of_is_0!(cssparser::TokenSerializationType, cssparser::SourceLocation, cssparser::SourcePosition);
 
 malloc_size_of_is_0!(selectors::OpaqueElement);
 
+/// Convenience extension trait for measuring a value without building a [`MallocSizeOfOps`]
+/// by hand. Only available when the `jemalloc-global` feature selects the allocator installed
+/// as the process's `#[global_allocator]`: jemalloc is the only global-allocator backend whose
+/// public API actually exposes a `usable_size`-style query (dlmalloc and wee_alloc don't).
+#[cfg(feature = "jemalloc-global")]
+pub trait MallocSizeOfExt: MallocSizeOf {
+    /// Measure `self`'s heap usage using the globally selected allocator.
+    fn malloc_size_of(&self) -> usize {
+        let mut ops = MallocSizeOfOps::new(global_usable_size, None, None);
+        self.size_of(&mut ops)
+    }
+}
+
+#[cfg(feature = "jemalloc-global")]
+impl<T: MallocSizeOf> MallocSizeOfExt for T {}
+
+#[cfg(feature = "jemalloc-global")]
+extern "C" {
+    #[cfg_attr(target_os = "macos", link_name = "malloc_usable_size")]
+    #[cfg_attr(not(target_os = "macos"), link_name = "je_malloc_usable_size")]
+    fn je_usable_size(ptr: *const c_void) -> usize;
+}
+
+/// `size_of_op` backed by jemalloc's `malloc_usable_size`.
+#[cfg(feature = "jemalloc-global")]
+unsafe extern "C" fn global_usable_size(ptr: *const c_void) -> usize {
+    je_usable_size(ptr)
+}
+
+// `usable-size-jemalloc` is distinct from `jemalloc-global` above: it backs
+// `MallocSizeOfOps::from_global_allocator()`, a full `ops` for embedders who want to pass it
+// into `value.size_of(&mut ops)` themselves, rather than the `MallocSizeOfExt` shortcut. There's
+// no `usable-size-dlmalloc`/`usable-size-weealloc` counterpart: neither allocator's public API
+// exposes a `usable_size`-style query, so those backends can only drive `new_estimate()`.
+#[cfg(feature = "usable-size-jemalloc")]
+extern "C" {
+    #[cfg_attr(target_os = "macos", link_name = "malloc_usable_size")]
+    #[cfg_attr(not(target_os = "macos"), link_name = "je_malloc_usable_size")]
+    fn je_usable_size_for_ops(ptr: *const c_void) -> usize;
+}
+
+#[cfg(feature = "usable-size-jemalloc")]
+unsafe extern "C" fn allocator_usable_size(ptr: *const c_void) -> usize {
+    je_usable_size_for_ops(ptr)
+}
+
 /// Measurable that defers to inner value and used to verify MallocSizeOf implementation in a
 /// struct.
 #[derive(Clone)]
@@ -841,3 +1283,115 @@ impl<T: MallocSizeOf> DerefMut for Measurable<T> {
         &mut self.0
     }
 }
+
+/// Verification helpers built on [`Measurable`], gated behind the `verify` feature. These are
+/// deliberately not `#[cfg(test)]`: they're meant to be called from a *consumer's* test suite
+/// (e.g. a `#[derive(MallocSizeOf)]`'d struct checking it didn't forget a field), so they need
+/// to be part of the compiled crate rather than only available while testing this crate itself.
+#[cfg(feature = "verify")]
+pub mod verify {
+    use super::{MallocConditionalSizeOf, MallocShallowSizeOf, MallocSizeOf, MallocSizeOfOps};
+    use core::ffi::c_void;
+
+    /// What went wrong when verifying a `MallocSizeOf` implementation.
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum VerifyError {
+        /// `shallow_size_of` reported more than `size_of`, which can only happen if the impl
+        /// double-counts its own shallow allocation as part of the deep total too.
+        ShallowExceedsDeep { shallow: usize, deep: usize },
+        /// The same value produced different results across two otherwise-identical
+        /// measurements, meaning the impl depends on something other than its own fields (e.g.
+        /// leftover `have_seen_ptr` state from a previous measurement).
+        NotRepeatable { first: usize, second: usize },
+        /// Measuring the same `Arc` a second time via `conditional_size_of`, sharing one
+        /// `have_seen_ptr` set, didn't report 0 -- its heap block would be double-counted when
+        /// reached through two paths in the same graph.
+        ArcNotDeduped { second_pass: usize },
+        /// Adding an element made the reported size shrink, or fall below the elements' own
+        /// stack footprint.
+        NotMonotonic {
+            len: usize,
+            reported: usize,
+            previous: usize,
+        },
+    }
+
+    /// Assert that `shallow_size_of(value) <= size_of(value)`, and that measuring `value` twice
+    /// with independent `ops` reports the same total both times.
+    pub fn verify_basic_invariants<T>(value: &T) -> Result<(), VerifyError>
+    where
+        T: MallocSizeOf + MallocShallowSizeOf,
+    {
+        let mut ops = MallocSizeOfOps::new_estimate();
+        let shallow = value.shallow_size_of(&mut ops);
+        let deep = value.size_of(&mut ops);
+        if shallow > deep {
+            return Err(VerifyError::ShallowExceedsDeep { shallow, deep });
+        }
+
+        let mut first_ops = MallocSizeOfOps::new_estimate();
+        let first = value.size_of(&mut first_ops);
+        let mut second_ops = MallocSizeOfOps::new_estimate();
+        let second = value.size_of(&mut second_ops);
+        if first != second {
+            return Err(VerifyError::NotRepeatable { first, second });
+        }
+
+        Ok(())
+    }
+
+    /// Assert that measuring `value` a second time through `conditional_size_of`, sharing one
+    /// `have_seen_ptr` set with the first measurement, charges its heap block only once.
+    #[cfg(feature = "std")]
+    pub fn verify_conditional_dedup<T>(value: &T) -> Result<(), VerifyError>
+    where
+        T: MallocConditionalSizeOf,
+    {
+        unsafe extern "C" fn constant_size(_ptr: *const c_void) -> usize {
+            8
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        let mut ops = MallocSizeOfOps::new(
+            constant_size,
+            Some(constant_size),
+            Some(std::boxed::Box::new(move |ptr: *const c_void| {
+                !seen.insert(ptr as usize)
+            })),
+        );
+
+        let _first_pass = value.conditional_size_of(&mut ops);
+        let second_pass = value.conditional_size_of(&mut ops);
+        if second_pass != 0 {
+            return Err(VerifyError::ArcNotDeduped { second_pass });
+        }
+
+        Ok(())
+    }
+
+    /// Proptest-style generator hook: build `Measurable<Vec<T>>`s of every length from 0 to
+    /// `max_len` using `make_element`, and confirm the reported size grows monotonically with
+    /// element count and never undercounts the elements' backing slice.
+    #[cfg(feature = "std")]
+    pub fn verify_monotonic_growth<T, F>(mut make_element: F, max_len: usize) -> Result<(), VerifyError>
+    where
+        T: MallocSizeOf,
+        F: FnMut(usize) -> T,
+    {
+        let mut ops = MallocSizeOfOps::new_estimate();
+        let mut previous = 0;
+        for len in 0..=max_len {
+            let elements: std::vec::Vec<T> = (0..len).map(&mut make_element).collect();
+            let lower_bound = core::mem::size_of::<T>() * len;
+            let reported = super::Measurable(elements).size_of(&mut ops);
+            if reported < previous {
+                return Err(VerifyError::NotMonotonic { len, reported, previous });
+            }
+            if reported < lower_bound {
+                return Err(VerifyError::NotMonotonic { len, reported, previous: lower_bound });
+            }
+            previous = reported;
+        }
+        Ok(())
+    }
+}
@@ -75,7 +75,7 @@ struct Resource {
     class: naga::AddressSpace,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum NumericDimension {
     Scalar,
     Vector(naga::VectorSize),
@@ -102,7 +102,7 @@ impl NumericDimension {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct NumericType {
     dim: NumericDimension,
     scalar: naga::Scalar,
@@ -123,6 +123,10 @@ impl fmt::Display for NumericType {
 #[derive(Clone, Debug)]
 pub struct InterfaceVar {
     pub ty: NumericType,
+    /// The shader-side name of this varying: the struct member name it was declared as, or the
+    /// argument name for a top-level entry point parameter. `None` for synthetic vars (e.g.
+    /// `vertex_attribute`) that don't come from a shader declaration.
+    name: Option<String>,
     interpolation: Option<naga::Interpolation>,
     sampling: Option<naga::Sampling>,
 }
@@ -131,6 +135,7 @@ impl InterfaceVar {
     pub fn vertex_attribute(format: wgt::VertexFormat) -> Self {
         InterfaceVar {
             ty: NumericType::from_vertex_format(format),
+            name: None,
             interpolation: None,
             sampling: None,
         }
@@ -139,6 +144,9 @@ impl InterfaceVar {
 
 impl fmt::Display for InterfaceVar {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref name) = self.name {
+            write!(f, "'{}' ", name)?;
+        }
         write!(
             f,
             "{} interpolated as {:?} with sampling {:?}",
@@ -149,15 +157,22 @@ impl fmt::Display for InterfaceVar {
 
 #[derive(Debug)]
 enum Varying {
-    Local { location: u32, iv: InterfaceVar },
+    Local {
+        location: u32,
+        iv: InterfaceVar,
+        /// Whether this is the second of a `@blend_src(0)`/`@blend_src(1)` pair of fragment
+        /// outputs sharing `location`, as used by dual-source blending.
+        second_blend_source: bool,
+    },
     BuiltIn(naga::BuiltIn),
 }
 
-#[allow(unused)]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct SpecializationConstant {
-    id: u32,
+    id: Option<u32>,
+    name: Option<String>,
     ty: NumericType,
+    has_default: bool,
 }
 
 #[derive(Debug, Default)]
@@ -165,13 +180,50 @@ struct EntryPoint {
     inputs: Vec<Varying>,
     outputs: Vec<Varying>,
     resources: Vec<naga::Handle<Resource>>,
-    #[allow(unused)]
+    /// This entry point's actual reads/writes of each of `resources`, gathered from its
+    /// `GlobalUse` -- tighter than the `naga::StorageAccess` the buffer's address space
+    /// declares, since a `var<storage, read_write>` binding might only ever be read from a
+    /// given stage.
+    resource_access: FastHashMap<naga::Handle<Resource>, naga::StorageAccess>,
+    /// Whether the entry point's body contains a subgroup operation (ballot, broadcast,
+    /// shuffle, or a collective reduction), which requires the device to support subgroups at
+    /// all, independent of any fixed subgroup size the pipeline requests.
+    uses_subgroup_operations: bool,
     spec_constants: Vec<SpecializationConstant>,
+    /// For each dimension of `workgroup_size`, the index into `spec_constants` of the override
+    /// it's computed from, if that dimension's `@workgroup_size` expression is a bare reference
+    /// to a single override. `None` either means that dimension isn't overridable, or that it's
+    /// some more complex expression we don't attempt to evaluate -- in the latter case we fall
+    /// back to trusting the value naga already resolved using the overrides' defaults.
+    workgroup_size_overrides: Option<[Option<usize>; 3]>,
     sampling_pairs: FastHashSet<(naga::Handle<Resource>, naga::Handle<Resource>)>,
+    /// The subset of `sampling_pairs` the entry point actually uses for a comparison sample
+    /// (i.e. `textureSampleCompare`/`textureSampleCompareLevel`, which lower to an
+    /// `Expression::ImageSample` with `depth_ref` set), as opposed to a regular sample.
+    comparison_sampling_pairs: FastHashSet<(naga::Handle<Resource>, naga::Handle<Resource>)>,
     workgroup_size: [u32; 3],
     dual_source_blending: bool,
 }
 
+impl SpecializationConstant {
+    /// A caller-supplied `constants` key identifies an override either by its numeric `@id`,
+    /// written out as a decimal string, or by its name.
+    fn matches_key(&self, key: &str) -> bool {
+        if let Ok(id) = key.parse::<u32>() {
+            if self.id == Some(id) {
+                return true;
+            }
+        }
+        self.name.as_deref() == Some(key)
+    }
+
+    fn display_name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| self.id.unwrap_or_default().to_string())
+    }
+}
+
 #[derive(Debug)]
 pub struct Interface {
     limits: wgt::Limits,
@@ -250,8 +302,11 @@ impl WebGpuError for FilteringError {
 pub enum InputError {
     #[error("Input is not provided by the earlier stage in the pipeline")]
     Missing,
-    #[error("Input type is not compatible with the provided {0}")]
-    WrongType(NumericType),
+    #[error("expects {expected} but the previous stage provides {provided}")]
+    WrongType {
+        expected: NumericType,
+        provided: NumericType,
+    },
     #[error("Input interpolation doesn't match provided {0:?}")]
     InterpolationMismatch(Option<naga::Interpolation>),
     #[error("Input sampling doesn't match provided {0:?}")]
@@ -279,8 +334,53 @@ pub enum StageError {
     },
     #[error("Shader uses {used} inter-stage components above the limit of {limit}")]
     TooManyVaryings { used: u32, limit: u32 },
+    #[error("Shader uses {used} inter-stage shader variables above the limit of {limit}")]
+    TooManyInterStageVariables { used: u32, limit: u32 },
+    #[error(
+        "Requested subgroup size {size} is not a power of two within the device's supported range [{min}, {max}]"
+    )]
+    SubgroupSizeOutOfRange { size: u32, min: u32, max: u32 },
+    #[error("Shader entry point '{0}' uses subgroup operations, which this device does not support")]
+    SubgroupOperationNotSupported(String),
+    #[error(
+        "Shader entry point '{0}' uses dual-source blending (@blend_src), which requires the DUAL_SOURCE_BLENDING feature"
+    )]
+    DualSourceBlendingFeatureDisabled(String),
+    #[error(
+        "Shader entry point '{entry_point}' uses dual-source blending but does not declare a @location(0) @blend_src({blend_src}) output"
+    )]
+    MissingBlendSrcOutput { entry_point: String, blend_src: u32 },
+    #[error(
+        "Shader entry point '{entry_point}' declares @location(0) @blend_src(0) and @blend_src(1) outputs of different types ({first} vs. {second}); dual-source blending requires them to match"
+    )]
+    BlendSrcTypeMismatch {
+        entry_point: String,
+        first: NumericType,
+        second: NumericType,
+    },
+    #[error(
+        "Shader entry point '{0}' uses dual-source blending, which requires exactly one color attachment (render target 0), but {1} were bound"
+    )]
+    WrongColorTargetCountForDualSource(String, u32),
+    #[error(
+        "Vertex shader entry point '{entry_point}' outputs @builtin(position) without @invariant, but the pipeline uses {compare:?} which requires the exact same depth value on every pass"
+    )]
+    NonInvariantPositionWithEqualityCompare {
+        entry_point: String,
+        compare: wgt::CompareFunction,
+    },
     #[error("Unable to find entry point '{0}'")]
     MissingEntryPoint(String),
+    #[error("Pipeline-overridable constant \"{0}\" has no default value and was not provided")]
+    MissingOverride(String),
+    #[error("Provided constant \"{key}\" does not match any pipeline-overridable constant declared by the shader")]
+    UnknownOverride { key: String },
+    #[error("Provided value {value} for pipeline-overridable constant \"{name}\" is not representable as {ty}")]
+    ConstantNotRepresentable {
+        name: String,
+        value: f64,
+        ty: NumericType,
+    },
     #[error("Shader global {0:?} is not available in the pipeline layout")]
     Binding(naga::ResourceBinding, #[source] BindingError),
     #[error("Unable to filter the texture ({texture:?}) by the sampler ({sampler:?})")]
@@ -290,7 +390,18 @@ pub enum StageError {
         #[source]
         error: FilteringError,
     },
-    #[error("Location[{location}] {var} is not provided by the previous stage outputs")]
+    #[error(
+        "Comparison sampler ({sampler:?}) paired with texture ({texture:?}) is inconsistent: \
+        the binding layout declares the sampler's comparison mode as {expected_comparison}, \
+        but the shader's use of the pair requires {got_comparison}"
+    )]
+    SamplerComparison {
+        texture: naga::ResourceBinding,
+        sampler: naga::ResourceBinding,
+        expected_comparison: bool,
+        got_comparison: bool,
+    },
+    #[error("Input @location({location}) {var}: {error}")]
     Input {
         location: wgt::ShaderLocation,
         var: InterfaceVar,
@@ -328,7 +439,19 @@ impl WebGpuError for StageError {
             } => error,
             Self::InvalidWorkgroupSize { .. }
             | Self::TooManyVaryings { .. }
+            | Self::TooManyInterStageVariables { .. }
+            | Self::SubgroupSizeOutOfRange { .. }
+            | Self::SubgroupOperationNotSupported(..)
+            | Self::DualSourceBlendingFeatureDisabled(..)
+            | Self::MissingBlendSrcOutput { .. }
+            | Self::BlendSrcTypeMismatch { .. }
+            | Self::WrongColorTargetCountForDualSource(..)
+            | Self::NonInvariantPositionWithEqualityCompare { .. }
             | Self::MissingEntryPoint(..)
+            | Self::MissingOverride(..)
+            | Self::UnknownOverride { .. }
+            | Self::ConstantNotRepresentable { .. }
+            | Self::SamplerComparison { .. }
             | Self::NoEntryPointFound
             | Self::MultipleEntryPointsFound => return ErrorType::Validation,
         };
@@ -627,13 +750,18 @@ impl Resource {
     fn derive_binding_type(
         &self,
         is_reffed_by_sampler_in_entrypoint: bool,
+        storage_access: naga::StorageAccess,
     ) -> Result<BindingType, BindingError> {
         Ok(match self.ty {
             ResourceType::Buffer { size } => BindingType::Buffer {
                 ty: match self.class {
                     naga::AddressSpace::Uniform => wgt::BufferBindingType::Uniform,
-                    naga::AddressSpace::Storage { access } => wgt::BufferBindingType::Storage {
-                        read_only: access == naga::StorageAccess::LOAD,
+                    naga::AddressSpace::Storage { .. } => wgt::BufferBindingType::Storage {
+                        // `storage_access` is the caller's merge of every entry point's actual
+                        // reads/writes to this binding, which can be tighter than the address
+                        // space's own declared `access` (e.g. a `var<storage, read_write>` that
+                        // a given stage only ever loads from).
+                        read_only: !storage_access.contains(naga::StorageAccess::STORE),
                     },
                     _ => return Err(BindingError::WrongBufferAddressSpace { space: self.class }),
                 },
@@ -866,16 +994,83 @@ impl NumericType {
     }
 }
 
-/// Return true if the fragment `format` is covered by the provided `output`.
+/// Return true if `value` can be losslessly expressed as an instance of `ty`, a scalar
+/// pipeline-overridable constant's declared type. WGSL overrides are always scalar, so any
+/// vector/matrix `ty` is rejected out of hand.
+fn override_value_representable(ty: &NumericType, value: f64) -> bool {
+    use naga::ScalarKind as Sk;
+
+    if !matches!(ty.dim, NumericDimension::Scalar) {
+        return false;
+    }
+    match ty.scalar.kind {
+        Sk::Bool => value == 0.0 || value == 1.0,
+        Sk::Sint => {
+            if !value.is_finite() || value.fract() != 0.0 {
+                return false;
+            }
+            let bits = u32::from(ty.scalar.width) * 8;
+            let (min, max) = if bits >= 64 {
+                (i64::MIN as f64, i64::MAX as f64)
+            } else {
+                (-(1i64 << (bits - 1)) as f64, ((1i64 << (bits - 1)) - 1) as f64)
+            };
+            value >= min && value <= max
+        }
+        Sk::Uint => {
+            if !value.is_finite() || value.fract() != 0.0 || value < 0.0 {
+                return false;
+            }
+            let bits = u32::from(ty.scalar.width) * 8;
+            let max = if bits >= 64 {
+                u64::MAX as f64
+            } else {
+                ((1u64 << bits) - 1) as f64
+            };
+            value <= max
+        }
+        Sk::Float => value.is_finite(),
+        Sk::AbstractInt | Sk::AbstractFloat => true,
+    }
+}
+
+/// A fragment shader color output at `location` whose numeric type isn't covered by the format
+/// of the render target bound to it.
+#[derive(Clone, Debug)]
+pub struct ColorAttachmentFormatMismatch {
+    pub location: wgt::ShaderLocation,
+    pub shader_type: NumericType,
+    pub format: wgt::TextureFormat,
+    pub format_type: NumericType,
+}
+
+impl fmt::Display for ColorAttachmentFormatMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "fragment output @location({}) is {} but target format {:?} provides {}",
+            self.location, self.shader_type, self.format, self.format_type
+        )
+    }
+}
+
+/// Return true if the fragment `format` bound at `location` is covered by the provided
+/// `output`.
 pub fn check_texture_format(
+    location: wgt::ShaderLocation,
     format: wgt::TextureFormat,
     output: &NumericType,
-) -> Result<(), NumericType> {
-    let nt = NumericType::from_texture_format(format);
-    if nt.is_subtype_of(output) {
+) -> Result<(), ColorAttachmentFormatMismatch> {
+    let format_type = NumericType::from_texture_format(format);
+    if format_type.is_subtype_of(output) {
         Ok(())
     } else {
-        Err(nt)
+        Err(ColorAttachmentFormatMismatch {
+            location,
+            shader_type: *output,
+            format,
+            format_type,
+        })
     }
 }
 
@@ -900,11 +1095,33 @@ impl<'a> BindingLayoutSource<'a> {
     }
 }
 
+/// How `check_stage` should react when it notices a shader interface hazard that isn't an
+/// outright binding mismatch -- something that's legal WebGPU but has historically caused
+/// driver-specific artifacts (e.g. a non-`@invariant` `@builtin(position)` output paired with
+/// an equality depth/stencil compare). Separate from [`StageError`]'s hard binding-mismatch
+/// variants so callers can opt into strict CI-style checking without changing runtime behavior
+/// for existing content.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HazardPolicy {
+    /// Don't check for the hazard at all.
+    Ignore,
+    /// Log a warning (the long-standing default behavior).
+    #[default]
+    Warn,
+    /// Fail validation with a [`StageError`].
+    Error,
+}
+
 pub type StageIo = FastHashMap<wgt::ShaderLocation, InterfaceVar>;
 
+/// Caller-supplied values for a shader module's pipeline-overridable (`constants`) constants,
+/// keyed by either the override's name or, as a decimal string, its numeric `@id`.
+pub type PipelineConstants = FastHashMap<String, f64>;
+
 impl Interface {
     fn populate(
         list: &mut Vec<Varying>,
+        name: Option<&str>,
         binding: Option<&naga::Binding>,
         ty: naga::Handle<naga::Type>,
         arena: &naga::UniqueArena<naga::Type>,
@@ -928,7 +1145,13 @@ impl Interface {
             },
             naga::TypeInner::Struct { ref members, .. } => {
                 for member in members {
-                    Self::populate(list, member.binding.as_ref(), member.ty, arena);
+                    Self::populate(
+                        list,
+                        member.name.as_deref(),
+                        member.binding.as_ref(),
+                        member.ty,
+                        arena,
+                    );
                 }
                 return;
             }
@@ -947,14 +1170,16 @@ impl Interface {
                 location,
                 interpolation,
                 sampling,
-                .. // second_blend_source
+                second_blend_source,
             }) => Varying::Local {
                 location,
                 iv: InterfaceVar {
                     ty: numeric_ty,
+                    name: name.map(str::to_string),
                     interpolation,
                     sampling,
                 },
+                second_blend_source,
             },
             Some(&naga::Binding::BuiltIn(built_in)) => Varying::BuiltIn(built_in),
             None => {
@@ -965,6 +1190,51 @@ impl Interface {
         list.push(varying);
     }
 
+    /// Follow a chain of loads back to the global variable `handle` ultimately reads from, or
+    /// `None` if it isn't a direct (or load-indirected) reference to one -- e.g. it came from a
+    /// binding array index or some other expression this isn't trying to reconstruct.
+    fn resolve_global_variable(
+        expressions: &naga::Arena<naga::Expression>,
+        handle: naga::Handle<naga::Expression>,
+    ) -> Option<naga::Handle<naga::GlobalVariable>> {
+        match expressions[handle] {
+            naga::Expression::GlobalVariable(var) => Some(var),
+            naga::Expression::Load { pointer } => Self::resolve_global_variable(expressions, pointer),
+            _ => None,
+        }
+    }
+
+    /// Whether `block`, or any block nested inside one of its statements, contains a subgroup
+    /// operation (ballot, a collective reduction, or a shuffle/broadcast gather).
+    fn block_uses_subgroup_operations(block: &naga::Block) -> bool {
+        block.iter().any(|stmt| match *stmt {
+            naga::Statement::SubgroupBallot { .. }
+            | naga::Statement::SubgroupCollectiveOperation { .. }
+            | naga::Statement::SubgroupGather { .. } => true,
+            naga::Statement::Block(ref nested) => Self::block_uses_subgroup_operations(nested),
+            naga::Statement::If {
+                ref accept,
+                ref reject,
+                ..
+            } => {
+                Self::block_uses_subgroup_operations(accept)
+                    || Self::block_uses_subgroup_operations(reject)
+            }
+            naga::Statement::Loop {
+                ref body,
+                ref continuing,
+                ..
+            } => {
+                Self::block_uses_subgroup_operations(body)
+                    || Self::block_uses_subgroup_operations(continuing)
+            }
+            naga::Statement::Switch { ref cases, .. } => cases
+                .iter()
+                .any(|case| Self::block_uses_subgroup_operations(&case.body)),
+            _ => false,
+        })
+    }
+
     pub fn new(module: &naga::Module, info: &naga::valid::ModuleInfo, limits: wgt::Limits) -> Self {
         let mut resources = naga::Arena::new();
         let mut resource_mapping = FastHashMap::default();
@@ -1010,17 +1280,46 @@ impl Interface {
             resource_mapping.insert(var_handle, handle);
         }
 
+        let mut overrides = Vec::with_capacity(module.overrides.len());
+        let mut override_index = FastHashMap::default();
+        for (handle, override_decl) in module.overrides.iter() {
+            let ty = match module.types[override_decl.ty].inner {
+                naga::TypeInner::Scalar(scalar) => NumericType {
+                    dim: NumericDimension::Scalar,
+                    scalar,
+                },
+                ref other => {
+                    log::warn!("Unexpected override type: {:?}", other);
+                    continue;
+                }
+            };
+            overrides.push(SpecializationConstant {
+                id: override_decl.id.map(u32::from),
+                name: override_decl.name.clone(),
+                ty,
+                has_default: override_decl.init.is_some(),
+            });
+            override_index.insert(handle, overrides.len() - 1);
+        }
+
         let mut entry_points = FastHashMap::default();
         entry_points.reserve(module.entry_points.len());
         for (index, entry_point) in module.entry_points.iter().enumerate() {
             let info = info.get_entry_point(index);
             let mut ep = EntryPoint::default();
             for arg in entry_point.function.arguments.iter() {
-                Self::populate(&mut ep.inputs, arg.binding.as_ref(), arg.ty, &module.types);
+                Self::populate(
+                    &mut ep.inputs,
+                    arg.name.as_deref(),
+                    arg.binding.as_ref(),
+                    arg.ty,
+                    &module.types,
+                );
             }
             if let Some(ref result) = entry_point.function.result {
                 Self::populate(
                     &mut ep.outputs,
+                    None,
                     result.binding.as_ref(),
                     result.ty,
                     &module.types,
@@ -1030,7 +1329,13 @@ impl Interface {
             for (var_handle, var) in module.global_variables.iter() {
                 let usage = info[var_handle];
                 if !usage.is_empty() && var.binding.is_some() {
-                    ep.resources.push(resource_mapping[&var_handle]);
+                    let handle = resource_mapping[&var_handle];
+                    ep.resources.push(handle);
+
+                    let mut access = naga::StorageAccess::empty();
+                    access.set(naga::StorageAccess::LOAD, usage.contains(naga::valid::GlobalUse::READ));
+                    access.set(naga::StorageAccess::STORE, usage.contains(naga::valid::GlobalUse::WRITE));
+                    ep.resource_access.insert(handle, access);
                 }
             }
 
@@ -1038,8 +1343,43 @@ impl Interface {
                 ep.sampling_pairs
                     .insert((resource_mapping[&key.image], resource_mapping[&key.sampler]));
             }
+            for (_, expression) in entry_point.function.expressions.iter() {
+                let naga::Expression::ImageSample {
+                    image,
+                    sampler,
+                    depth_ref: Some(_),
+                    ..
+                } = *expression
+                else {
+                    continue;
+                };
+                let pair = Self::resolve_global_variable(&entry_point.function.expressions, image)
+                    .zip(Self::resolve_global_variable(
+                        &entry_point.function.expressions,
+                        sampler,
+                    ));
+                if let Some((image_var, sampler_var)) = pair {
+                    ep.comparison_sampling_pairs.insert((
+                        resource_mapping[&image_var],
+                        resource_mapping[&sampler_var],
+                    ));
+                }
+            }
             ep.dual_source_blending = info.dual_source_blending;
+            ep.uses_subgroup_operations =
+                Self::block_uses_subgroup_operations(&entry_point.function.body);
             ep.workgroup_size = entry_point.workgroup_size;
+            ep.spec_constants = overrides.clone();
+            ep.workgroup_size_overrides = entry_point.workgroup_size_overrides.map(|dims| {
+                dims.map(|dim| {
+                    dim.and_then(|expr| match module.global_expressions[expr] {
+                        naga::Expression::Override(override_handle) => {
+                            override_index.get(&override_handle).copied()
+                        }
+                        _ => None,
+                    })
+                })
+            });
 
             entry_points.insert((entry_point.stage, entry_point.name.clone()), ep);
         }
@@ -1073,6 +1413,18 @@ impl Interface {
             })
     }
 
+    /// The number of inter-stage components `built_in` costs against
+    /// `max_inter_stage_shader_components`, for the handful of built-ins that are backed by
+    /// real varying slots on at least one backend rather than being purely fixed-function.
+    /// Everything else is free.
+    fn builtin_component_cost(built_in: naga::BuiltIn) -> u32 {
+        match built_in {
+            naga::BuiltIn::Position { .. } => 4,
+            naga::BuiltIn::ClipDistance | naga::BuiltIn::CullDistance => 1,
+            _ => 0,
+        }
+    }
+
     pub(crate) fn shader_stage_from_stage_bit(stage_bit: wgt::ShaderStages) -> naga::ShaderStage {
         match stage_bit {
             wgt::ShaderStages::VERTEX => naga::ShaderStage::Vertex,
@@ -1082,6 +1434,29 @@ impl Interface {
         }
     }
 
+    /// The `@location`s the fragment entry point `entry_point_name` reads from the previous
+    /// stage. A vertex shader output at a location missing from this set is never read by the
+    /// paired fragment shader, so the backend generating the vertex stage is free to drop it
+    /// from the output struct it emits -- see `check_stage`'s `unconsumed_outputs` parameter.
+    pub fn fragment_input_locations(
+        &self,
+        entry_point_name: &str,
+    ) -> Result<FastHashSet<wgt::ShaderLocation>, StageError> {
+        let pair = (naga::ShaderStage::Fragment, entry_point_name.to_string());
+        let entry_point = self
+            .entry_points
+            .get(&pair)
+            .ok_or_else(|| StageError::MissingEntryPoint(pair.1.clone()))?;
+        Ok(entry_point
+            .inputs
+            .iter()
+            .filter_map(|input| match *input {
+                Varying::Local { location, .. } => Some(location),
+                Varying::BuiltIn(_) => None,
+            })
+            .collect())
+    }
+
     pub fn check_stage(
         &self,
         layouts: &mut BindingLayoutSource<'_>,
@@ -1090,6 +1465,14 @@ impl Interface {
         stage_bit: wgt::ShaderStages,
         inputs: StageIo,
         compare_function: Option<wgt::CompareFunction>,
+        constants: &PipelineConstants,
+        subgroup_size: Option<u32>,
+        subgroup_operations_supported: bool,
+        fragment_input_locations: &FastHashSet<wgt::ShaderLocation>,
+        unconsumed_outputs: &mut FastHashSet<wgt::ShaderLocation>,
+        features: wgt::Features,
+        color_target_count: u32,
+        invariant_position_hazard_policy: HazardPolicy,
     ) -> Result<StageIo, StageError> {
         // Since a shader module can have multiple entry points with the same name,
         // we need to look for one with the right execution model.
@@ -1138,22 +1521,46 @@ impl Interface {
                             break 'err Err(BindingError::Missing);
                         };
 
+                        let storage_access = entry_point
+                            .resource_access
+                            .get(&handle)
+                            .copied()
+                            // Conservative fallback: treat it as read-write rather than assert a
+                            // narrower access than we can actually account for.
+                            .unwrap_or(naga::StorageAccess::LOAD | naga::StorageAccess::STORE);
                         let ty = match res.derive_binding_type(
                             entry_point
                                 .sampling_pairs
                                 .iter()
                                 .any(|&(im, _samp)| im == handle),
+                            storage_access,
                         ) {
                             Ok(ty) => ty,
                             Err(error) => break 'err Err(error),
                         };
 
                         match map.entry(res.bind.binding) {
-                            indexmap::map::Entry::Occupied(e) if e.get().ty != ty => {
-                                break 'err Err(BindingError::InconsistentlyDerivedType)
-                            }
                             indexmap::map::Entry::Occupied(e) => {
-                                e.into_mut().visibility |= stage_bit;
+                                let existing = e.into_mut();
+                                if let (
+                                    BindingType::Buffer {
+                                        ty: wgt::BufferBindingType::Storage { read_only: existing_read_only },
+                                        ..
+                                    },
+                                    BindingType::Buffer {
+                                        ty: wgt::BufferBindingType::Storage { read_only },
+                                        ..
+                                    },
+                                ) = (&mut existing.ty, &ty)
+                                {
+                                    // A binding read in one stage and written in another
+                                    // derives as read-write overall: merge by ANDing, not by
+                                    // rejecting the mismatch as inconsistent.
+                                    *existing_read_only &= read_only;
+                                } else if existing.ty != ty {
+                                    break 'err Err(BindingError::InconsistentlyDerivedType);
+                                }
+                                existing.visibility |= stage_bit;
                             }
                             indexmap::map::Entry::Vacant(e) => {
                                 e.insert(BindGroupLayoutEntry {
@@ -1194,6 +1601,10 @@ impl Interface {
                     sampler_layout.ty,
                     BindingType::Sampler(wgt::SamplerBindingType::Filtering)
                 );
+                let sampler_comparison = matches!(
+                    sampler_layout.ty,
+                    BindingType::Sampler(wgt::SamplerBindingType::Comparison)
+                );
                 let texture_sample_type = match texture_layout.ty {
                     BindingType::Texture { sample_type, .. } => sample_type,
                     BindingType::ExternalTexture => {
@@ -1218,6 +1629,95 @@ impl Interface {
                         error,
                     });
                 }
+
+                // A comparison sampler only makes sense paired with a depth texture, and a
+                // comparison sample (`textureSampleCompare`) in the shader body requires the
+                // pipeline layout to actually provide a comparison sampler for that pair.
+                let shader_uses_comparison = entry_point
+                    .comparison_sampling_pairs
+                    .contains(&(texture_handle, sampler_handle));
+                if sampler_comparison
+                    && !matches!(texture_sample_type, wgt::TextureSampleType::Depth)
+                {
+                    return Err(StageError::SamplerComparison {
+                        texture: *texture_bind,
+                        sampler: *sampler_bind,
+                        expected_comparison: true,
+                        got_comparison: false,
+                    });
+                }
+                if shader_uses_comparison && !sampler_comparison {
+                    return Err(StageError::SamplerComparison {
+                        texture: *texture_bind,
+                        sampler: *sampler_bind,
+                        expected_comparison: false,
+                        got_comparison: true,
+                    });
+                }
+            }
+        }
+
+        // Resolve the caller-supplied pipeline-overridable constants against the overrides
+        // declared by the shader, rejecting unknown keys, unrepresentable values, and
+        // defaultless overrides that weren't provided.
+        let mut resolved_overrides: Vec<Option<f64>> = entry_point
+            .spec_constants
+            .iter()
+            .map(|_| None)
+            .collect();
+        for (key, &value) in constants.iter() {
+            let Some(override_index) = entry_point
+                .spec_constants
+                .iter()
+                .position(|sc| sc.matches_key(key))
+            else {
+                return Err(StageError::UnknownOverride { key: key.clone() });
+            };
+            resolved_overrides[override_index] = Some(value);
+        }
+        for (sc, resolved) in entry_point
+            .spec_constants
+            .iter()
+            .zip(resolved_overrides.iter())
+        {
+            match *resolved {
+                Some(value) if !override_value_representable(&sc.ty, value) => {
+                    return Err(StageError::ConstantNotRepresentable {
+                        name: sc.display_name(),
+                        value,
+                        ty: sc.ty,
+                    });
+                }
+                None if !sc.has_default => {
+                    return Err(StageError::MissingOverride(sc.display_name()));
+                }
+                _ => {}
+            }
+        }
+
+        // Pipeline-overridable constants can appear directly in `@workgroup_size`; recompute
+        // the concrete size from any overrides the caller actually supplied before checking it
+        // against the limits below. Dimensions that aren't overridden, or whose expression we
+        // don't recognize as a bare override reference, keep naga's statically-resolved value.
+        let mut workgroup_size = entry_point.workgroup_size;
+        if let Some(dim_overrides) = entry_point.workgroup_size_overrides {
+            for (dim, dim_override) in workgroup_size.iter_mut().zip(dim_overrides) {
+                if let Some(value) = dim_override.and_then(|index| resolved_overrides[index]) {
+                    *dim = value as u32;
+                }
+            }
+        }
+
+        if entry_point.uses_subgroup_operations && !subgroup_operations_supported {
+            return Err(StageError::SubgroupOperationNotSupported(
+                entry_point_name.to_string(),
+            ));
+        }
+        if let Some(size) = subgroup_size {
+            let min = self.limits.min_subgroup_size;
+            let max = self.limits.max_subgroup_size;
+            if !size.is_power_of_two() || size < min || size > max {
+                return Err(StageError::SubgroupSizeOutOfRange { size, min, max });
             }
         }
 
@@ -1228,16 +1728,16 @@ impl Interface {
                 self.limits.max_compute_workgroup_size_y,
                 self.limits.max_compute_workgroup_size_z,
             ];
-            let total_invocations = entry_point.workgroup_size.iter().product::<u32>();
+            let total_invocations = workgroup_size.iter().product::<u32>();
 
-            if entry_point.workgroup_size.contains(&0)
+            if workgroup_size.contains(&0)
                 || total_invocations > self.limits.max_compute_invocations_per_workgroup
-                || entry_point.workgroup_size[0] > max_workgroup_size_limits[0]
-                || entry_point.workgroup_size[1] > max_workgroup_size_limits[1]
-                || entry_point.workgroup_size[2] > max_workgroup_size_limits[2]
+                || workgroup_size[0] > max_workgroup_size_limits[0]
+                || workgroup_size[1] > max_workgroup_size_limits[1]
+                || workgroup_size[2] > max_workgroup_size_limits[2]
             {
                 return Err(StageError::InvalidWorkgroupSize {
-                    current: entry_point.workgroup_size,
+                    current: workgroup_size,
                     current_total: total_invocations,
                     limit: max_workgroup_size_limits,
                     total: self.limits.max_compute_invocations_per_workgroup,
@@ -1246,11 +1746,12 @@ impl Interface {
         }
 
         let mut inter_stage_components = 0;
+        let mut inter_stage_variables = 0;
 
         // check inputs compatibility
         for input in entry_point.inputs.iter() {
             match *input {
-                Varying::Local { location, ref iv } => {
+                Varying::Local { location, ref iv, .. } => {
                     let result =
                         inputs
                             .get(&location)
@@ -1289,12 +1790,19 @@ impl Interface {
                                 if compatible {
                                     Ok(num_components)
                                 } else {
-                                    Err(InputError::WrongType(provided.ty))
+                                    Err(InputError::WrongType {
+                                        expected: iv.ty,
+                                        provided: provided.ty,
+                                    })
                                 }
                             });
                     match result {
                         Ok(num_components) => {
                             inter_stage_components += num_components;
+                            // vertex inputs don't count towards inter-stage
+                            if shader_stage != naga::ShaderStage::Vertex {
+                                inter_stage_variables += 1;
+                            }
                         }
                         Err(error) => {
                             return Err(StageError::Input {
@@ -1311,41 +1819,102 @@ impl Interface {
 
         if shader_stage == naga::ShaderStage::Vertex {
             for output in entry_point.outputs.iter() {
-                //TODO: count builtins towards the limit?
                 inter_stage_components += match *output {
                     Varying::Local { ref iv, .. } => iv.ty.dim.num_components(),
-                    Varying::BuiltIn(_) => 0,
+                    Varying::BuiltIn(built_in) => Self::builtin_component_cost(built_in),
                 };
 
+                if let Varying::Local { location, .. } = *output {
+                    inter_stage_variables += 1;
+                    if !fragment_input_locations.contains(&location) {
+                        unconsumed_outputs.insert(location);
+                    }
+                }
+
                 if let Some(
                     cmp @ wgt::CompareFunction::Equal | cmp @ wgt::CompareFunction::NotEqual,
                 ) = compare_function
                 {
                     if let Varying::BuiltIn(naga::BuiltIn::Position { invariant: false }) = *output
                     {
-                        log::warn!(
-                            "Vertex shader with entry point {entry_point_name} outputs a @builtin(position) without the @invariant \
-                            attribute and is used in a pipeline with {cmp:?}. On some machines, this can cause bad artifacting as {cmp:?} assumes \
-                            the values output from the vertex shader exactly match the value in the depth buffer. The @invariant attribute on the \
-                            @builtin(position) vertex output ensures that the exact same pixel depths are used every render."
-                        );
+                        match invariant_position_hazard_policy {
+                            HazardPolicy::Ignore => {}
+                            HazardPolicy::Warn => log::warn!(
+                                "Vertex shader with entry point {entry_point_name} outputs a @builtin(position) without the @invariant \
+                                attribute and is used in a pipeline with {cmp:?}. On some machines, this can cause bad artifacting as {cmp:?} assumes \
+                                the values output from the vertex shader exactly match the value in the depth buffer. The @invariant attribute on the \
+                                @builtin(position) vertex output ensures that the exact same pixel depths are used every render."
+                            ),
+                            HazardPolicy::Error => {
+                                return Err(StageError::NonInvariantPositionWithEqualityCompare {
+                                    entry_point: entry_point_name.to_string(),
+                                    compare: cmp,
+                                })
+                            }
+                        }
                     }
                 }
             }
         }
 
+        if shader_stage == naga::ShaderStage::Fragment && entry_point.dual_source_blending {
+            if !features.contains(wgt::Features::DUAL_SOURCE_BLENDING) {
+                return Err(StageError::DualSourceBlendingFeatureDisabled(
+                    entry_point_name.to_string(),
+                ));
+            }
+            if color_target_count != 1 {
+                return Err(StageError::WrongColorTargetCountForDualSource(
+                    entry_point_name.to_string(),
+                    color_target_count,
+                ));
+            }
+
+            let blend_src_output = |second_blend_source| {
+                entry_point.outputs.iter().find_map(|output| match *output {
+                    Varying::Local {
+                        location: 0,
+                        ref iv,
+                        second_blend_source: matches,
+                    } if matches == second_blend_source => Some(iv.ty),
+                    _ => None,
+                })
+            };
+            let first = blend_src_output(false).ok_or_else(|| StageError::MissingBlendSrcOutput {
+                entry_point: entry_point_name.to_string(),
+                blend_src: 0,
+            })?;
+            let second = blend_src_output(true).ok_or_else(|| StageError::MissingBlendSrcOutput {
+                entry_point: entry_point_name.to_string(),
+                blend_src: 1,
+            })?;
+            if first != second {
+                return Err(StageError::BlendSrcTypeMismatch {
+                    entry_point: entry_point_name.to_string(),
+                    first,
+                    second,
+                });
+            }
+        }
+
         if inter_stage_components > self.limits.max_inter_stage_shader_components {
             return Err(StageError::TooManyVaryings {
                 used: inter_stage_components,
                 limit: self.limits.max_inter_stage_shader_components,
             });
         }
+        if inter_stage_variables > self.limits.max_inter_stage_shader_variables {
+            return Err(StageError::TooManyInterStageVariables {
+                used: inter_stage_variables,
+                limit: self.limits.max_inter_stage_shader_variables,
+            });
+        }
 
         let outputs = entry_point
             .outputs
             .iter()
             .filter_map(|output| match *output {
-                Varying::Local { location, ref iv } => Some((location, iv.clone())),
+                Varying::Local { location, ref iv, .. } => Some((location, iv.clone())),
                 Varying::BuiltIn(_) => None,
             })
             .collect();
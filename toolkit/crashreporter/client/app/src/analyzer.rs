@@ -0,0 +1,133 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Pre-submission minidump symbolication via the bundled `minidump-analyzer` tool.
+
+use crate::std::path::Path;
+use crate::std::process::{Command, Stdio};
+use crate::std::time::{Duration, Instant};
+use crate::std;
+use anyhow::Context;
+
+/// How long to let `minidump-analyzer` run before giving up on it.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Run `minidump-analyzer` on `dump_file` and merge the stack traces and module list it
+/// produces into `extra_file`, preserving every key already there.
+///
+/// Older installations don't ship `minidump-analyzer`; in that case this silently does
+/// nothing, so the report is still submitted, just without symbolicated stacks.
+pub fn analyze_and_merge(dump_file: &Path, extra_file: &Path) {
+    let analyzer_path = crate::config::installation_program_path("minidump-analyzer");
+    if !analyzer_path.exists() {
+        log::debug!(
+            "minidump-analyzer not present at {}, skipping analysis",
+            analyzer_path.display()
+        );
+        return;
+    }
+
+    match run_analyzer(&analyzer_path, dump_file) {
+        Ok(Some(analysis)) => merge_into_extra_file(extra_file, analysis),
+        Ok(None) => log::warn!(
+            "minidump-analyzer produced no output for {}",
+            dump_file.display()
+        ),
+        Err(e) => log::warn!(
+            "minidump-analyzer failed for {}: {e:#}",
+            dump_file.display()
+        ),
+    }
+}
+
+/// Spawn `analyzer_path` on `dump_file` and collect its stdout as JSON, killing it if it
+/// doesn't finish within `TIMEOUT`.
+fn run_analyzer(analyzer_path: &Path, dump_file: &Path) -> anyhow::Result<Option<serde_json::Value>> {
+    let mut child = Command::new(analyzer_path)
+        .arg(dump_file)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to spawn {}", analyzer_path.display()))?;
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > TIMEOUT {
+            log::warn!("minidump-analyzer exceeded {TIMEOUT:?}, killing it");
+            child.kill().ok();
+            child.wait().ok();
+            anyhow::bail!("timed out after {TIMEOUT:?}");
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    if let Some(mut out) = child.stdout.take() {
+        std::io::Read::read_to_end(&mut out, &mut stdout).ok();
+    }
+    if let Some(mut err) = child.stderr.take() {
+        std::io::Read::read_to_end(&mut err, &mut stderr).ok();
+    }
+    if !stderr.is_empty() {
+        log::debug!(
+            "minidump-analyzer stderr: {}",
+            String::from_utf8_lossy(&stderr)
+        );
+    }
+
+    if !status.success() {
+        anyhow::bail!("exited with {status}");
+    }
+    if stdout.is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_slice(&stdout)
+        .map(Some)
+        .context("failed to parse analyzer output as JSON")
+}
+
+/// Merge `analysis` into the extra file at `extra_file`, overwriting only the keys the
+/// analyzer actually produced (e.g. `StackTraces`) and leaving everything else untouched.
+fn merge_into_extra_file(extra_file: &Path, analysis: serde_json::Value) {
+    let Some(mut extra) = std::fs::read(extra_file)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).ok())
+    else {
+        log::warn!(
+            "couldn't read extra file {} to merge analysis into",
+            extra_file.display()
+        );
+        return;
+    };
+
+    let Some(analysis) = analysis.as_object() else {
+        log::warn!("minidump-analyzer output wasn't a JSON object, ignoring it");
+        return;
+    };
+    let Some(extra_obj) = extra.as_object_mut() else {
+        log::warn!(
+            "extra file {} wasn't a JSON object, ignoring analysis",
+            extra_file.display()
+        );
+        return;
+    };
+    for (key, value) in analysis {
+        extra_obj.insert(key.clone(), value.clone());
+    }
+
+    if let Err(e) = serde_json::to_vec_pretty(&extra)
+        .map_err(anyhow::Error::from)
+        .and_then(|bytes| std::fs::write(extra_file, bytes).map_err(anyhow::Error::from))
+    {
+        log::warn!(
+            "failed to write merged extra file {}: {e}",
+            extra_file.display()
+        );
+    }
+}
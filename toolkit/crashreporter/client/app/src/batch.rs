@@ -0,0 +1,90 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Support for processing a backlog of queued crashes in a single invocation.
+
+use crate::std::path::{Path, PathBuf};
+use crate::std;
+
+/// The per-crash state needed to load, move, submit and prune a single queued crash,
+/// independent of any other crash in the same batch.
+#[derive(Clone, Debug)]
+pub struct CrashJob {
+    pub dump_file: PathBuf,
+    pub extra_file: PathBuf,
+    pub memory_file: Option<PathBuf>,
+}
+
+impl CrashJob {
+    /// Build a job from a dump file, deriving the extra/memory paths the same way a
+    /// single-crash `Config` would.
+    pub fn from_dump_file(dump_file: PathBuf) -> Self {
+        let mut extra_file = dump_file.clone();
+        extra_file.set_extension("extra");
+        let mut memory_file = dump_file.clone();
+        memory_file.set_extension("memory.json.gz");
+        let memory_file = memory_file.exists().then_some(memory_file);
+        CrashJob {
+            dump_file,
+            extra_file,
+            memory_file,
+        }
+    }
+}
+
+/// Enumerate every `.dmp` file directly under `dir`, producing one job per dump.
+pub fn enumerate_pending(dir: &Path) -> anyhow::Result<Vec<CrashJob>> {
+    let mut jobs = Vec::new();
+    for entry in dir
+        .read_dir()
+        .map_err(|e| anyhow::anyhow!("failed to read batch directory {}: {e}", dir.display()))?
+    {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::error!("error while iterating over {} directory entry: {e}", dir.display());
+                continue;
+            }
+        };
+        let path = entry.path();
+        if path.extension() == Some("dmp".as_ref()) {
+            jobs.push(CrashJob::from_dump_file(path));
+        }
+    }
+    Ok(jobs)
+}
+
+/// The outcome of processing one job in a batch.
+pub struct JobResult {
+    pub dump_file: PathBuf,
+    pub result: anyhow::Result<()>,
+}
+
+/// A summary of a batch run: jobs that succeeded and jobs that failed (with their errors),
+/// so that one bad crash doesn't abort the rest of the backlog.
+#[derive(Default)]
+pub struct BatchSummary {
+    pub succeeded: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, anyhow::Error)>,
+}
+
+impl BatchSummary {
+    pub fn record(&mut self, result: JobResult) {
+        match result.result {
+            Ok(()) => self.succeeded.push(result.dump_file),
+            Err(e) => self.failed.push((result.dump_file, e)),
+        }
+    }
+}
+
+impl std::fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} succeeded, {} failed",
+            self.succeeded.len(),
+            self.failed.len()
+        )
+    }
+}
@@ -0,0 +1,168 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Optional compression of crash report files at rest and before upload.
+
+use crate::std::path::{Path, PathBuf};
+use crate::std::{self, fs::File};
+
+/// The codec used to compress a stored file, recorded alongside the extra JSON so the
+/// upload path knows which `Content-Encoding` to send.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    Zstd,
+    Xz,
+}
+
+impl Codec {
+    /// The value written into the extra JSON's `Compression` field.
+    pub fn extra_value(self) -> &'static str {
+        match self {
+            Codec::Zstd => "zstd",
+            Codec::Xz => "xz",
+        }
+    }
+
+    /// The value to send as the upload's `Content-Encoding` header.
+    pub fn content_encoding(self) -> &'static str {
+        match self {
+            Codec::Zstd => "zstd",
+            Codec::Xz => "xz",
+        }
+    }
+
+    /// The extension appended to a compressed file's name.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Codec::Zstd => "zst",
+            Codec::Xz => "xz",
+        }
+    }
+}
+
+/// Tunables for the configured codec, read from the environment in
+/// `Config::read_from_environment`.
+#[derive(Clone, Copy, Debug)]
+pub struct CompressionConfig {
+    pub codec: Codec,
+    /// zstd compression level, or the xz preset level (0-9).
+    pub level: u32,
+    /// zstd long-distance-matching window log, in bits.
+    pub zstd_window_log: u32,
+    /// xz dictionary window size, in bytes (e.g. 64 MiB vs. the 8 MiB default).
+    pub xz_dict_size: u32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            codec: Codec::Zstd,
+            level: 19,
+            zstd_window_log: 27,
+            xz_dict_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Compress `from` into `to` (which gets `codec`'s extension appended) using the given
+/// tunables. Returns the path of the compressed file on success.
+///
+/// If the encoder fails for any reason, this logs a warning and falls back to copying the
+/// file uncompressed, since crash handling should never fail purely because of compression.
+pub fn compress_file(from: &Path, to: &Path, config: &CompressionConfig) -> std::io::Result<PathBuf> {
+    let mut compressed_to = to.as_os_str().to_owned();
+    compressed_to.push(".");
+    compressed_to.push(config.codec.extension());
+    let compressed_to = PathBuf::from(compressed_to);
+
+    match try_compress_file(from, &compressed_to, config) {
+        Ok(()) => Ok(compressed_to),
+        Err(e) => {
+            log::warn!(
+                "failed to compress {} with {:?}, storing uncompressed: {e}",
+                from.display(),
+                config.codec
+            );
+            std::fs::copy(from, to)?;
+            Ok(to.to_owned())
+        }
+    }
+}
+
+fn try_compress_file(from: &Path, to: &Path, config: &CompressionConfig) -> std::io::Result<()> {
+    let mut source = File::open(from)?;
+    let dest = File::create(to)?;
+    let mut encoder = new_encoder(dest, config)?;
+
+    let mut buf = [0u8; COPY_BUFFER_SIZE];
+    loop {
+        let n = std::io::Read::read(&mut source, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        std::io::Write::write_all(&mut encoder, &buf[..n])?;
+    }
+    encoder.finish()
+}
+
+/// A sink that finishes (flushes trailing codec frames) on `finish`, wrapping the
+/// destination `File` so callers can stream writes through the chosen codec.
+trait Encoder: std::io::Write {
+    fn finish(self: Box<Self>) -> std::io::Result<()>;
+}
+
+fn new_encoder(dest: File, config: &CompressionConfig) -> std::io::Result<Box<dyn Encoder>> {
+    match config.codec {
+        Codec::Zstd => {
+            let mut encoder = zstd::Encoder::new(dest, config.level as i32)?;
+            encoder.multithread(0).ok();
+            encoder.long_distance_matching(true)?;
+            encoder.window_log(config.zstd_window_log)?;
+            struct ZstdEncoder<'a>(zstd::Encoder<'a, File>);
+            impl<'a> std::io::Write for ZstdEncoder<'a> {
+                fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                    self.0.write(buf)
+                }
+                fn flush(&mut self) -> std::io::Result<()> {
+                    self.0.flush()
+                }
+            }
+            impl<'a> Encoder for ZstdEncoder<'a> {
+                fn finish(self: Box<Self>) -> std::io::Result<()> {
+                    self.0.finish()?;
+                    Ok(())
+                }
+            }
+            Ok(Box::new(ZstdEncoder(encoder)))
+        }
+        Codec::Xz => {
+            let mut filters = xz2::stream::Filters::new();
+            filters.lzma2(
+                xz2::stream::LzmaOptions::new_preset(config.level)?
+                    .dict_size(config.xz_dict_size),
+            );
+            let stream = xz2::stream::Stream::new_stream_encoder(&filters, xz2::stream::Check::Crc64)?;
+            struct XzEncoder(xz2::write::XzEncoder<File>);
+            impl std::io::Write for XzEncoder {
+                fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                    self.0.write(buf)
+                }
+                fn flush(&mut self) -> std::io::Result<()> {
+                    self.0.flush()
+                }
+            }
+            impl Encoder for XzEncoder {
+                fn finish(self: Box<Self>) -> std::io::Result<()> {
+                    self.0.finish()?;
+                    Ok(())
+                }
+            }
+            Ok(Box::new(XzEncoder(xz2::write::XzEncoder::new_stream(
+                dest, stream,
+            ))))
+        }
+    }
+}
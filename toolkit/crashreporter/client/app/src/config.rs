@@ -7,14 +7,37 @@
 use crate::std::borrow::Cow;
 use crate::std::ffi::{OsStr, OsString};
 use crate::std::path::{Path, PathBuf};
-use crate::std::process::Command;
-use crate::{lang, logging::LogTarget, std};
+use crate::{
+    analyzer, batch, compression, exe_resolve, lang, libname, logging::LogTarget, runner, stddirs,
+    std,
+};
 use anyhow::Context;
 use once_cell::sync::Lazy;
 
 /// The number of the most recent minidump files to retain when pruning.
 const MINIDUMP_PRUNE_SAVE_COUNT: usize = 10;
 
+/// Limits governing which pending minidumps `prune_files` keeps.
+#[derive(Clone, Copy, Debug)]
+struct PruneLimits {
+    /// Maximum number of minidumps (and their siblings) to retain.
+    max_count: usize,
+    /// Maximum total bytes across all retained dumps, extras and memory files, if set.
+    max_bytes: Option<u64>,
+    /// Maximum age, in days, of a retained dump, if set.
+    max_age_days: Option<u64>,
+}
+
+impl Default for PruneLimits {
+    fn default() -> Self {
+        PruneLimits {
+            max_count: MINIDUMP_PRUNE_SAVE_COUNT,
+            max_bytes: None,
+            max_age_days: None,
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     pub const MINIDUMP_PRUNE_SAVE_COUNT: usize = super::MINIDUMP_PRUNE_SAVE_COUNT;
@@ -30,22 +53,28 @@ pub mod test {
 
             #[test]
             fn data_dir_root_xdg_default() {
+                // With no `XDG_STATE_HOME`/`XDG_DATA_HOME` set and no legacy directory on
+                // disk, we fall back to the XDG spec's own default for state data rather
+                // than the legacy `~/.config` location.
                 mock::builder()
                     .set(env::MockHomeDir, "home_dir".into())
                     .run(|| {
                         let path = cfg_get_data_dir_root();
-                        assert_eq!(path, crate::std::path::PathBuf::from("home_dir/.config/vendor"));
+                        assert_eq!(path, crate::std::path::PathBuf::from("home_dir/.local/state/vendor"));
                      });
             }
 
             #[test]
-            fn data_dir_root_xdg_home() {
+            fn data_dir_root_ignores_xdg_config_home() {
+                // `XDG_CONFIG_HOME` is for configuration, not state; it no longer has any
+                // bearing on where crash reports are stored now that the `XDG_STATE_HOME`
+                // spec default is always available when `home_dir` is known.
                 mock::builder()
                     .set(env::MockHomeDir, "home_dir".into())
                     .set(env::MockEnv("XDG_CONFIG_HOME".into()), "home_dir/xdg/config".into())
                     .run(|| {
                         let path = cfg_get_data_dir_root();
-                        assert_eq!(path, crate::std::path::PathBuf::from("home_dir/xdg/config/vendor"));
+                        assert_eq!(path, crate::std::path::PathBuf::from("home_dir/.local/state/vendor"));
                     });
             }
 
@@ -62,6 +91,8 @@ pub mod test {
 
             #[test]
             fn data_dir_root_legacy_existing() {
+                // With no `XDG_STATE_HOME`/`XDG_DATA_HOME` set, an existing legacy directory
+                // is still used and left in place rather than migrated.
                 let mock_files = MockFiles::new();
                 mock_files.add_dir("home_dir").add_dir("home_dir/.vendor");
 
@@ -71,6 +102,57 @@ pub mod test {
                     .run(|| {
                         let path = cfg_get_data_dir_root();
                         assert_eq!(path, crate::std::path::PathBuf::from("home_dir/.vendor"));
+                        assert!(crate::std::path::Path::new("home_dir/.vendor").exists());
+                    });
+            }
+
+            #[test]
+            fn data_dir_root_xdg_state_home() {
+                mock::builder()
+                    .set(env::MockHomeDir, "home_dir".into())
+                    .set(env::MockEnv("XDG_STATE_HOME".into()), "home_dir/xdg/state".into())
+                    .run(|| {
+                        let path = cfg_get_data_dir_root();
+                        assert_eq!(path, crate::std::path::PathBuf::from("home_dir/xdg/state/vendor"));
+                    });
+            }
+
+            #[test]
+            fn data_dir_root_xdg_data_home() {
+                mock::builder()
+                    .set(env::MockHomeDir, "home_dir".into())
+                    .set(env::MockEnv("XDG_DATA_HOME".into()), "home_dir/xdg/data".into())
+                    .run(|| {
+                        let path = cfg_get_data_dir_root();
+                        assert_eq!(path, crate::std::path::PathBuf::from("home_dir/xdg/data/vendor"));
+                    });
+            }
+
+            #[test]
+            fn data_dir_root_xdg_state_home_takes_priority_over_data_home() {
+                mock::builder()
+                    .set(env::MockHomeDir, "home_dir".into())
+                    .set(env::MockEnv("XDG_STATE_HOME".into()), "home_dir/xdg/state".into())
+                    .set(env::MockEnv("XDG_DATA_HOME".into()), "home_dir/xdg/data".into())
+                    .run(|| {
+                        let path = cfg_get_data_dir_root();
+                        assert_eq!(path, crate::std::path::PathBuf::from("home_dir/xdg/state/vendor"));
+                    });
+            }
+
+            #[test]
+            fn data_dir_root_xdg_migrates_legacy() {
+                let mock_files = MockFiles::new();
+                mock_files.add_dir("home_dir").add_dir("home_dir/.vendor");
+
+                mock::builder()
+                    .set(env::MockHomeDir, "home_dir".into())
+                    .set(env::MockEnv("XDG_STATE_HOME".into()), "home_dir/xdg/state".into())
+                    .set(MockFS, mock_files.clone())
+                    .run(|| {
+                        let path = cfg_get_data_dir_root();
+                        assert_eq!(path, crate::std::path::PathBuf::from("home_dir/xdg/state/vendor"));
+                        assert!(!crate::std::path::Path::new("home_dir/.vendor").exists());
                     });
             }
         }
@@ -120,6 +202,14 @@ pub struct Config {
     pub strings: Option<lang::LangStrings>,
     /// The log target.
     pub log_target: Option<LogTarget>,
+    /// Configuration for compressing minidumps and their companion files at rest and
+    /// before upload, if enabled.
+    pub compression: Option<compression::CompressionConfig>,
+    /// The limits used to decide which pending minidumps `prune_files` keeps.
+    prune_limits: PruneLimits,
+    /// When running in batch mode (multiple dump paths or `--batch <dir>` on the command
+    /// line), the remaining crashes to process after the current `dump_file`.
+    pub batch_jobs: Vec<batch::CrashJob>,
 }
 
 pub struct ConfigStringBuilder<'a>(lang::LangStringBuilder<'a>);
@@ -176,12 +266,56 @@ impl Config {
 
         self.report_url = std::env::var_os(ekey!("URL"));
 
+        self.compression = env_bool(ekey!("COMPRESS_MINIDUMPS")).then(|| {
+            let mut config = compression::CompressionConfig::default();
+            if let Some(level) = env_u64(ekey!("COMPRESSION_LEVEL")) {
+                config.level = level as u32;
+            }
+            if let Some(window_log) = env_u64(ekey!("COMPRESSION_ZSTD_WINDOW_LOG")) {
+                config.zstd_window_log = window_log as u32;
+            }
+            if let Some(dict_size) = env_u64(ekey!("COMPRESSION_XZ_DICT_SIZE")) {
+                config.xz_dict_size = dict_size as u32;
+            }
+            config
+        });
+
+        self.prune_limits = PruneLimits {
+            max_count: env_u64(ekey!("PRUNE_MAX_COUNT"))
+                .map(|n| n as usize)
+                .unwrap_or(MINIDUMP_PRUNE_SAVE_COUNT),
+            max_bytes: env_u64(ekey!("PRUNE_MAX_BYTES")),
+            max_age_days: env_u64(ekey!("PRUNE_MAX_AGE_DAYS")),
+        };
+
         let mut args = std::env::args_os()
             // skip program name
             .skip(1);
-        self.dump_file = args.next().map(|p| p.into());
-        while let Some(arg) = args.next() {
-            log::warn!("ignoring extraneous argument: {}", arg.to_string_lossy());
+        match args.next() {
+            Some(arg) if arg == "--batch" => {
+                let Some(dir) = args.next() else {
+                    log::error!("--batch requires a directory argument");
+                    return;
+                };
+                match batch::enumerate_pending(Path::new(&dir)) {
+                    Ok(jobs) => self.batch_jobs = jobs,
+                    Err(e) => log::error!("failed to enumerate batch directory: {e:#}"),
+                }
+            }
+            first_arg => {
+                let mut dump_files: Vec<PathBuf> =
+                    first_arg.into_iter().map(PathBuf::from).collect();
+                dump_files.extend(args.map(PathBuf::from));
+
+                self.dump_file = dump_files.first().cloned();
+                if dump_files.len() > 1 {
+                    self.batch_jobs = dump_files
+                        .into_iter()
+                        .skip(1)
+                        .map(batch::CrashJob::from_dump_file)
+                        .collect();
+                }
+            }
         }
 
         self.strings = Some(lang::load());
@@ -214,6 +348,11 @@ impl Config {
     pub fn load_extra_file(&mut self) -> anyhow::Result<serde_json::Value> {
         let extra_file = self.extra_file().unwrap();
 
+        // Symbolicate the dump and merge the resulting stack traces into the extra file
+        // before we read it back, so callers see them without any server-side processing.
+        // This is a no-op (and logs, rather than fails) if minidump-analyzer isn't shipped.
+        analyzer::analyze_and_merge(self.dump_file(), &extra_file);
+
         // Load the extra file (which minidump-analyzer just updated).
         let extra: serde_json::Value =
             serde_json::from_reader(std::fs::File::open(&extra_file).with_context(|| {
@@ -309,6 +448,26 @@ impl Config {
         self.data_dir.as_deref().unwrap()
     }
 
+    /// The directory holding pending crash reports, honoring the configured data directory
+    /// if one is set, otherwise falling back to the platform's standard per-user data
+    /// directory for the default vendor/product. Useful when launched standalone, before any
+    /// dump file (and thus the extra file's actual vendor/product) is known.
+    pub fn pending_crashes_dir(&self) -> Option<PathBuf> {
+        self.data_dir
+            .clone()
+            .map(|dir| dir.join("pending"))
+            .or_else(|| stddirs::pending_crashes_dir(DEFAULT_VENDOR, DEFAULT_PRODUCT))
+    }
+
+    /// The default profile root, honoring the profile directory discovered from the extra
+    /// file if one is set, otherwise falling back to the platform's standard per-user data
+    /// directory for the default vendor/product.
+    pub fn profile_root(&self) -> Option<PathBuf> {
+        self.profile_dir
+            .clone()
+            .or_else(|| stddirs::profile_root(DEFAULT_VENDOR, DEFAULT_PRODUCT))
+    }
+
     /// The path to the dump file.
     ///
     /// Panics if no dump file is set.
@@ -323,6 +482,44 @@ impl Config {
         self.dump_file().file_stem().unwrap().to_string_lossy()
     }
 
+    /// Advance to the next queued crash in `batch_jobs`, if any, resetting the per-crash
+    /// state (dump/extra/memory paths and the profile directory loaded from its extra
+    /// file) while keeping the rest of the configuration (data dir, strings, etc.) intact.
+    ///
+    /// Returns `false` once the batch is exhausted.
+    pub fn advance_to_next_job(&mut self) -> bool {
+        let Some(job) = self.batch_jobs.pop() else {
+            return false;
+        };
+        self.dump_file = Some(job.dump_file);
+        self.profile_dir = None;
+        true
+    }
+
+    /// Drive every queued crash — the current `dump_file` plus anything left in
+    /// `batch_jobs` — through `process_one`, one at a time, recording each outcome in a
+    /// [`batch::BatchSummary`] instead of stopping at the first failure. `process_one` sees
+    /// the per-crash state (`dump_file`, `extra_file`, `memory_file`, `profile_dir`) already
+    /// switched to the job it's handling, so it can call the same single-crash methods
+    /// (`move_crash_data_to_pending`, `delete_files`, etc.) used outside of batch mode.
+    pub fn process_batch(
+        &mut self,
+        mut process_one: impl FnMut(&mut Self) -> anyhow::Result<()>,
+    ) -> batch::BatchSummary {
+        let mut summary = batch::BatchSummary::default();
+        if self.dump_file.is_none() {
+            self.advance_to_next_job();
+        }
+        while let Some(dump_file) = self.dump_file.clone() {
+            let result = process_one(self);
+            summary.record(batch::JobResult { dump_file, result });
+            if !self.advance_to_next_job() {
+                break;
+            }
+        }
+        summary
+    }
+
     /// Move crash data to the pending folder.
     pub fn move_crash_data_to_pending(&mut self) -> anyhow::Result<()> {
         let pending_crashes_dir = self.data_dir().join("pending");
@@ -332,8 +529,25 @@ impl Config {
                 .get()
         })?;
 
-        let move_file = |from: &Path| -> anyhow::Result<PathBuf> {
+        let move_file = |from: &Path, compress: bool| -> anyhow::Result<PathBuf> {
             let to = pending_crashes_dir.join(from.file_name().unwrap());
+
+            if compress {
+                if let Some(compression) = &self.compression {
+                    let to = compression::compress_file(from, &to, compression)
+                        .with_context(|| {
+                            self.build_string("crashreporter-error-moving-path")
+                                .arg("from", from.display().to_string())
+                                .arg("to", to.display().to_string())
+                                .get()
+                        })?;
+                    if let Err(e) = std::fs::remove_file(from) {
+                        log::warn!("failed to remove {}: {e}", from.display());
+                    }
+                    return Ok(to);
+                }
+            }
+
             // Try to rename, but copy and remove if it fails. `rename` won't work across
             // mount points. (bug 506009)
             if let Err(e) = std::fs::rename(from, &to) {
@@ -353,11 +567,11 @@ impl Config {
             Ok(to)
         };
 
-        let new_dump_file = move_file(self.dump_file())?;
-        move_file(self.extra_file().unwrap().as_ref())?;
+        let new_dump_file = move_file(self.dump_file(), true)?;
+        let new_extra_file = move_file(self.extra_file().unwrap().as_ref(), false)?;
         // Failing to move the memory file is recoverable.
         if let Some(memory_file) = self.memory_file() {
-            if let Err(e) = move_file(memory_file.as_ref()) {
+            if let Err(e) = move_file(memory_file.as_ref(), true) {
                 log::warn!("failed to move memory file: {e}");
                 if let Err(e) = std::fs::remove_file(&memory_file) {
                     log::warn!("failed to remove {}: {e}", memory_file.display());
@@ -365,6 +579,16 @@ impl Config {
             }
         }
 
+        if let Some(compression) = &self.compression {
+            let was_compressed = new_dump_file.extension()
+                == Some(std::ffi::OsStr::new(compression.codec.extension()));
+            if was_compressed {
+                if let Err(e) = record_compression_codec(&new_extra_file, compression.codec) {
+                    log::warn!("failed to record compression codec in extra file: {e:#}");
+                }
+            }
+        }
+
         self.dump_file = Some(new_dump_file);
 
         Ok(())
@@ -397,8 +621,15 @@ impl Config {
     }
 
     /// Prune old minidump files adjacent to the dump file.
+    ///
+    /// Newest-first, a dump (and its sibling extra/memory files) is kept only while it is
+    /// within `prune_limits`' count, cumulative byte size and age limits; the first entry to
+    /// exceed any one of them, and everything older, is deleted.
     pub fn prune_files(&self) -> anyhow::Result<()> {
-        log::info!("pruning minidump files to the {MINIDUMP_PRUNE_SAVE_COUNT} most recent");
+        log::info!(
+            "pruning minidump files to {:?}",
+            self.prune_limits
+        );
         let Some(file) = &self.dump_file else {
             anyhow::bail!("no dump file")
         };
@@ -442,12 +673,49 @@ impl Config {
         // of identical times). The reverse leaves the files in order from newest to oldest.
         minidump_files.sort_unstable_by(|a, b| a.cmp(b).reverse());
 
-        // Delete files, skipping the most recent MINIDUMP_PRUNE_SAVE_COUNT.
-        for dump_file in minidump_files
-            .into_iter()
-            .skip(MINIDUMP_PRUNE_SAVE_COUNT)
-            .map(|v| v.1)
-        {
+        let now = std::time::SystemTime::now();
+        let max_age = self
+            .prune_limits
+            .max_age_days
+            .map(|days| std::time::Duration::from_secs(days * 24 * 60 * 60));
+
+        let mut cumulative_bytes: u64 = 0;
+        let mut past_limit = false;
+        for (index, (modified_time, dump_file)) in minidump_files.into_iter().enumerate() {
+            let siblings = [
+                dump_file.clone(),
+                extra_file_for_dump_file(dump_file.clone()),
+                memory_file_for_dump_file(dump_file.clone()),
+            ];
+            let entry_bytes: u64 = siblings
+                .iter()
+                .filter_map(|p| std::fs::metadata(p).ok())
+                .map(|m| m.len())
+                .sum();
+
+            let too_old = max_age
+                .map(|max_age| {
+                    now.duration_since(modified_time)
+                        .map(|age| age > max_age)
+                        .unwrap_or(false)
+                })
+                .unwrap_or(false);
+
+            let keep = !past_limit
+                && index < self.prune_limits.max_count
+                && self
+                    .prune_limits
+                    .max_bytes
+                    .map(|max_bytes| cumulative_bytes + entry_bytes <= max_bytes)
+                    .unwrap_or(true)
+                && !too_old;
+
+            if keep {
+                cumulative_bytes += entry_bytes;
+                continue;
+            }
+            past_limit = true;
+
             log::debug!("pruning {} and related files", dump_file.display());
             if let Err(e) = std::fs::remove_file(&dump_file) {
                 log::warn!("failed to delete {}: {e}", dump_file.display());
@@ -462,22 +730,18 @@ impl Config {
 
     /// Restart the program based on the configured restart command.
     pub fn restart_process(&self) {
-        if self.restart_command.is_none() {
+        let Some(restart_command) = &self.restart_command else {
             // The restart button should be hidden in this case, so this error should not occur.
             log::error!("no process configured for restart");
             return;
-        }
+        };
 
-        let mut cmd = Command::new(self.restart_command.as_ref().unwrap());
-        cmd.args(&self.restart_args)
-            .stdin(std::process::Stdio::null())
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null());
-        if let Some(xul_app_file) = &self.app_file {
-            cmd.env("XUL_APP_FILE", xul_app_file);
-        }
-        log::debug!("restarting process: {:?}", cmd);
-        if let Err(e) = cmd.spawn() {
+        let runner = runner::firefox_relauncher(
+            restart_command,
+            &self.restart_args,
+            self.app_file.as_deref(),
+        );
+        if let Err(e) = runner.start() {
             log::error!("failed to restart process: {e}");
         }
     }
@@ -498,10 +762,21 @@ impl Config {
         let legacy_data = home_dir
             .clone()
             .map(|h| h.join(format!(".{}", vendor.to_lowercase())));
-        let data_path = if std::env::var_os("MOZ_LEGACY_HOME").is_some()
-            || legacy_data.as_ref().expect("No HOME env?").exists()
-        {
-            legacy_data
+        let legacy_exists = legacy_data.as_ref().expect("No HOME env?").exists();
+
+        // An existing legacy directory keeps being used unless `XDG_STATE_HOME`/`XDG_DATA_HOME`
+        // is explicitly set; only then (or when there is no legacy directory) is the XDG
+        // location used, falling back to the `~/.local/state` spec default.
+        let xdg_explicitly_set = std::env::var_os("XDG_STATE_HOME").is_some()
+            || std::env::var_os("XDG_DATA_HOME").is_some();
+        let xdg_state_or_data_home = xdg_base_dir(&home_dir);
+
+        let data_path = if std::env::var_os("MOZ_LEGACY_HOME").is_some() {
+            legacy_data.clone()
+        } else if legacy_exists && !xdg_explicitly_set {
+            legacy_data.clone()
+        } else if let Some(xdg_home) = xdg_state_or_data_home {
+            Some(xdg_home.join(format!("{}", vendor.to_lowercase())))
         } else {
             std::env::var_os("XDG_CONFIG_HOME")
                 .map(PathBuf::from)
@@ -509,6 +784,13 @@ impl Config {
                 .map(|h| h.join(format!("{}", vendor.to_lowercase())))
         }
         .with_context(|| self.string("crashreporter-error-no-home-dir"))?;
+
+        // If the legacy directory still exists on disk but an XDG location is now
+        // configured, migrate it once so future runs stop consulting the old path.
+        if legacy_exists && data_path != *legacy_data.as_ref().unwrap() {
+            migrate_data_dir(legacy_data.as_ref().unwrap(), &data_path);
+        }
+
         Ok(data_path)
     }
 
@@ -656,6 +938,14 @@ pub fn installation_path() -> &'static Path {
             if let Some(ancestor) = dir_path.ancestors().nth(3) {
                 return ancestor;
             }
+            // The resolved path didn't have enough ancestors to be a bundle-within-a-bundle,
+            // which means it isn't laid out the way we expect; fall through to treating it as
+            // a bare executable rather than silently returning the wrong directory.
+            log::warn!(
+                "expected {} to be nested inside a Firefox.app bundle, but it doesn't have \
+                 enough parent directories",
+                dir_path.display()
+            );
         }
 
         dir_path
@@ -663,19 +953,28 @@ pub fn installation_path() -> &'static Path {
     &*PATH
 }
 
+/// Get the path of a shared library shipped in the installation.
+///
+/// Handles the per-platform library naming convention and, on macOS, the split between the
+/// bundle's `MacOS` and `Resources` directories.
+///
+/// The returned path isn't guaranteed to exist.
+pub fn installation_library_path(stem: &str) -> PathBuf {
+    let name = libname::shared_library_name(stem);
+    let path = installation_path().join(&name);
+    if cfg!(all(not(mock), target_os = "macos")) && !path.exists() {
+        return installation_resource_path().join(&name);
+    }
+    path
+}
+
 /// Read the buildid from the installation.
 ///
 /// This may fail if installation files are not found.
 pub fn buildid() -> Option<&'static str> {
     static BUILDID: Lazy<Option<String>> = Lazy::new(|| {
         let section_name = buildid_section::MOZ_BUILDID_SECTION_NAME.to_str().ok()?;
-        let xul_path = installation_path().join(if cfg!(target_os = "macos") {
-            "XUL"
-        } else if cfg!(target_os = "windows") {
-            "xul.dll"
-        } else {
-            "libxul.so"
-        });
+        let xul_path = installation_library_path("XUL");
         #[cfg(mock)]
         let xul_path = xul_path.as_ref();
         match buildid_reader::BuildIdReader::new(&xul_path)
@@ -695,15 +994,7 @@ pub fn buildid() -> Option<&'static str> {
 }
 
 fn self_path() -> &'static Path {
-    static PATH: Lazy<PathBuf> = Lazy::new(|| {
-        // Expect shouldn't ever panic here because we need more than one argument to run
-        // the program in the first place (we've already previously iterated args).
-        //
-        // We use argv[0] rather than `std::env::current_exe` because `current_exe` doesn't define
-        // how symlinks are treated, and we want to support running directly from the local build
-        // directory (which uses symlinks on linux and macos).
-        PathBuf::from(std::env::args_os().next().expect("failed to get argv[0]"))
-    });
+    static PATH: Lazy<PathBuf> = Lazy::new(exe_resolve::current_exe_resolved);
     &*PATH
 }
 
@@ -715,6 +1006,85 @@ fn env_path<K: AsRef<OsStr>>(name: K) -> Option<PathBuf> {
     std::env::var_os(name).map(PathBuf::from)
 }
 
+fn env_u64<K: AsRef<OsStr>>(name: K) -> Option<u64> {
+    std::env::var(name).ok().and_then(|s| s.parse().ok())
+}
+
+/// Resolve the XDG base directory that crash reports should live under, per the XDG Base
+/// Directory spec: an explicit `XDG_STATE_HOME` takes priority, since crash reports are
+/// restart-spanning application state, followed by an explicit `XDG_DATA_HOME`. Only once
+/// neither is set do we fall back to the `XDG_STATE_HOME` spec default of `~/.local/state`,
+/// since state is still the better fit than data for a directory nobody configured. Returns
+/// `None` only when neither variable is set and `home_dir` is `None` either.
+#[cfg(all(target_os = "linux", any(not(mock), test)))]
+fn xdg_base_dir(home_dir: &Option<PathBuf>) -> Option<PathBuf> {
+    if let Some(state_home) = std::env::var_os("XDG_STATE_HOME") {
+        return Some(PathBuf::from(state_home));
+    }
+    if let Some(data_home) = std::env::var_os("XDG_DATA_HOME") {
+        return Some(PathBuf::from(data_home));
+    }
+    home_dir.as_ref().map(|home| home.join(".local/state"))
+}
+
+/// Move the `Crash Reports` tree (and everything else under the legacy vendor directory)
+/// from `legacy_dir` to `new_dir`, reusing the rename-then-copy-and-remove strategy from
+/// `move_crash_data_to_pending` so the migration works across mount points. Best-effort:
+/// failures are logged but never block startup.
+#[cfg(all(target_os = "linux", any(not(mock), test)))]
+fn migrate_data_dir(legacy_dir: &Path, new_dir: &Path) {
+    log::info!(
+        "migrating crash report data from {} to {}",
+        legacy_dir.display(),
+        new_dir.display()
+    );
+    if let Some(parent) = new_dir.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            log::warn!("failed to create {}: {e}", parent.display());
+            return;
+        }
+    }
+    if let Err(e) = std::fs::rename(legacy_dir, new_dir) {
+        log::warn!(
+            "failed to rename {} to {}: {e}, trying to copy and remove instead",
+            legacy_dir.display(),
+            new_dir.display()
+        );
+        if let Err(e) = copy_dir_recursive(legacy_dir, new_dir) {
+            log::warn!("failed to migrate crash report data: {e}");
+            return;
+        }
+        if let Err(e) = std::fs::remove_dir_all(legacy_dir) {
+            log::warn!("failed to remove {} after migration: {e}", legacy_dir.display());
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", any(not(mock), test)))]
+fn copy_dir_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in from.read_dir()? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_recursive(&entry.path(), &dest)?;
+        } else {
+            std::fs::copy(entry.path(), dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Record which codec a pending dump/memory file was compressed with in the extra JSON, so
+/// the submission path knows to set `Content-Encoding` when uploading it.
+fn record_compression_codec(extra_file: &Path, codec: compression::Codec) -> anyhow::Result<()> {
+    let mut extra: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(extra_file)?)?;
+    extra["Compression"] = serde_json::Value::String(codec.extra_value().to_owned());
+    serde_json::to_writer(std::fs::File::create(extra_file)?, &extra)?;
+    Ok(())
+}
+
 fn extra_file_for_dump_file(mut dump_file: PathBuf) -> PathBuf {
     dump_file.set_extension("extra");
     dump_file
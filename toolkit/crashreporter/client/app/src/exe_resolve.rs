@@ -0,0 +1,47 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Resolve the crash reporter's own executable path.
+//!
+//! `argv[0]` is normally trustworthy and, unlike `std::env::current_exe`, preserves the
+//! symlink layout used by local (non-packaged) builds, where the installed binary is a
+//! symlink back into the object directory. But it breaks down when the reporter is invoked
+//! through a relative name, a shell wrapper that replaces `argv[0]`, or a symlink farm with
+//! multiple indirections. This resolves the executable more resiliently: try `argv[0]`,
+//! fall back to `current_exe`, then follow the binary's own symlink chain -- but not its
+//! containing directories, so symlinked directories above the binary stay as they are. A
+//! binary that is itself a symlink resolves to its target, and so to the target's directory.
+
+use crate::std::path::PathBuf;
+use crate::std;
+
+/// Resolve the path to the currently running executable.
+pub fn current_exe_resolved() -> PathBuf {
+    let argv0 = std::env::args_os().next().map(PathBuf::from);
+    let candidate = argv0
+        .filter(|p| p.is_file())
+        .or_else(|| std::env::current_exe().ok())
+        // Only panics if `argv[0]` doesn't name an existing file and `current_exe` fails too.
+        .expect("failed to resolve the running executable's path");
+    resolve_binary_symlink(candidate)
+}
+
+/// Follow `path`'s own symlink chain to the real binary, without canonicalizing any of its
+/// parent directories.
+fn resolve_binary_symlink(mut path: PathBuf) -> PathBuf {
+    // Bound the number of hops so a symlink loop can't hang us.
+    for _ in 0..32 {
+        match path.read_link() {
+            Ok(target) if target.is_relative() => {
+                path = path
+                    .parent()
+                    .map(|parent| parent.join(&target))
+                    .unwrap_or(target);
+            }
+            Ok(target) => path = target,
+            Err(_) => break,
+        }
+    }
+    path
+}
@@ -0,0 +1,287 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Localization of the crash reporter UI.
+//!
+//! Strings are looked up, in order: the bundled Fluent catalog for the current locale, a
+//! Fluent langpack shipped in the crashed profile, and finally a legacy flat key/value
+//! catalog for profiles/builds that predate the Fluent migration. The first source with a
+//! value for the requested id wins; if none have it, the bare id is returned.
+
+use crate::std::borrow::Cow;
+use crate::std::path::Path;
+use crate::std;
+use fluent_bundle::{FluentArgs, FluentBundle, FluentResource, FluentValue};
+use unic_langid::LanguageIdentifier;
+
+/// A fully loaded set of localization sources for the crash reporter.
+pub struct LangStrings {
+    current_locale: LanguageIdentifier,
+    catalog: FluentBundle<FluentResource>,
+    langpack: Option<FluentBundle<FluentResource>>,
+    legacy: legacy::LegacyStrings,
+}
+
+/// Builds up the arguments for a single localized string lookup.
+pub struct LangStringBuilder<'a> {
+    strings: &'a LangStrings,
+    id: &'a str,
+    args: Vec<(&'a str, Cow<'a, str>)>,
+}
+
+impl<'a> LangStringBuilder<'a> {
+    /// Set an argument used to fill in a placeable in the looked-up string.
+    pub fn arg<V: Into<Cow<'a, str>>>(mut self, key: &'a str, value: V) -> Self {
+        self.args.push((key, value.into()));
+        self
+    }
+
+    fn fluent_args(&self) -> FluentArgs<'a> {
+        let mut args = FluentArgs::new();
+        for (key, value) in &self.args {
+            args.set(*key, FluentValue::from(value.clone()));
+        }
+        args
+    }
+
+    /// Resolve the string, falling back through the catalog, langpack and legacy sources,
+    /// and finally to the bare id if none of them have it.
+    pub fn get(self) -> String {
+        let fluent_args = self.fluent_args();
+        for bundle in [Some(&self.strings.catalog), self.strings.langpack.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(msg) = bundle.get_message(self.id) {
+                if let Some(pattern) = msg.value() {
+                    let mut errors = Vec::new();
+                    let value = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+                    for e in errors {
+                        log::warn!("error formatting fluent string {}: {e}", self.id);
+                    }
+                    return value.into_owned();
+                }
+            }
+        }
+
+        if let Some(value) = self.strings.legacy.lookup(self.id, &self.args) {
+            return value;
+        }
+
+        self.id.to_owned()
+    }
+}
+
+impl LangStrings {
+    pub fn builder<'a>(&'a self, id: &'a str) -> LangStringBuilder<'a> {
+        LangStringBuilder {
+            strings: self,
+            id,
+            args: Vec::new(),
+        }
+    }
+
+    /// Whether the configured language has right-to-left text flow.
+    pub fn is_rtl(&self) -> bool {
+        // `unic-langid`'s script/region heuristics aren't consulted here; this mirrors the
+        // crash reporter's own locale list rather than a general BIDI judgment.
+        matches!(self.current_locale.language.as_str(), "ar" | "fa" | "he" | "ur")
+    }
+
+    /// Load localization strings from a Fluent langpack shipped in a crashed profile,
+    /// replacing any previously loaded langpack.
+    pub fn add_langpack(&mut self, profile_dir: &Path, locale: Option<&str>) -> anyhow::Result<()> {
+        let locale = locale.unwrap_or(self.current_locale.language.as_str());
+        let path = profile_dir
+            .join("langpacks")
+            .join(locale)
+            .join("crashreporter.ftl");
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("failed to read langpack {}: {e}", path.display()))?;
+        let resource = FluentResource::try_new(source)
+            .map_err(|(_, errors)| anyhow::anyhow!("failed to parse langpack {}: {errors:?}", path.display()))?;
+        let mut bundle = FluentBundle::new(vec![self.current_locale.clone()]);
+        bundle
+            .add_resource(resource)
+            .map_err(|errors| anyhow::anyhow!("failed to add langpack resource: {errors:?}"))?;
+        self.langpack = Some(bundle);
+        Ok(())
+    }
+}
+
+/// Load the crash reporter's localization data for the current system locale.
+pub fn load() -> LangStrings {
+    let current_locale: LanguageIdentifier = std::env::var("LANG")
+        .ok()
+        .and_then(|s| s.split('.').next().map(str::to_owned))
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| "en-US".parse().unwrap());
+
+    let source = include_str!("../locales/en-US/crashreporter.ftl").to_owned();
+    let resource =
+        FluentResource::try_new(source).unwrap_or_else(|(res, errors)| {
+            log::error!("errors parsing bundled crashreporter.ftl: {errors:?}");
+            res
+        });
+    let mut catalog = FluentBundle::new(vec![current_locale.clone()]);
+    if let Err(errors) = catalog.add_resource(resource) {
+        log::error!("errors adding bundled crashreporter.ftl resource: {errors:?}");
+    }
+
+    LangStrings {
+        current_locale,
+        catalog,
+        langpack: None,
+        legacy: legacy::load(),
+    }
+}
+
+mod legacy {
+    //! Fallback for profiles/builds that predate the Fluent migration: a flat key/value
+    //! `crashreporter.ini`-style catalog, mapped onto current Fluent ids via a small set of
+    //! textual transforms.
+
+    use crate::std::borrow::Cow;
+    use crate::std::collections::HashMap;
+    use crate::std;
+
+    /// A transform applied, in order, to a legacy value before it's returned.
+    enum Transform {
+        /// Split on the literal `\n\n` paragraph separator used by the old catalog to pack
+        /// several Fluent messages into a single old message, and keep one segment.
+        SplitParagraph { segment: usize },
+        /// Substitute a fixed substring (e.g. an old `%s` positional marker) with a
+        /// `{ $var }`-style placeable that `arg()` can then fill.
+        Replace { from: &'static str, to: &'static str },
+    }
+
+    /// Maps a current Fluent id to the legacy key it's derived from and the transforms
+    /// needed to produce the new-style value from the old one.
+    struct LegacyMapping {
+        legacy_key: &'static str,
+        transforms: &'static [Transform],
+    }
+
+    // The old catalog packed several related messages into one string, e.g.
+    // `crashReporterErrorText` became both `crashreporter-error-opening-file` (segment 0) and
+    // `crashreporter-error-loading-file` (segment 1), and used `%s` where Fluent now uses
+    // `{ $path }`.
+    static LEGACY_MAPPINGS: &[(&str, LegacyMapping)] = &[
+        (
+            "crashreporter-error-opening-file",
+            LegacyMapping {
+                legacy_key: "crashReporterErrorText",
+                transforms: &[
+                    Transform::SplitParagraph { segment: 0 },
+                    Transform::Replace { from: "%s", to: "{ $path }" },
+                ],
+            },
+        ),
+        (
+            "crashreporter-error-loading-file",
+            LegacyMapping {
+                legacy_key: "crashReporterErrorText",
+                transforms: &[
+                    Transform::SplitParagraph { segment: 1 },
+                    Transform::Replace { from: "%s", to: "{ $path }" },
+                ],
+            },
+        ),
+        (
+            "crashreporter-error-creating-dir",
+            LegacyMapping {
+                legacy_key: "crashReporterDirErrorText",
+                transforms: &[Transform::Replace { from: "%s", to: "{ $path }" }],
+            },
+        ),
+        (
+            "crashreporter-error-moving-path",
+            LegacyMapping {
+                legacy_key: "crashReporterMoveErrorText",
+                transforms: &[
+                    Transform::Replace { from: "%1$s", to: "{ $from }" },
+                    Transform::Replace { from: "%2$s", to: "{ $to }" },
+                ],
+            },
+        ),
+        (
+            "crashreporter-error-no-home-dir",
+            LegacyMapping {
+                legacy_key: "crashReporterNoHomeDirText",
+                transforms: &[],
+            },
+        ),
+    ];
+
+    pub struct LegacyStrings {
+        /// The raw legacy key/value catalog, if one was found on disk.
+        values: HashMap<String, String>,
+    }
+
+    impl LegacyStrings {
+        /// Resolve `id` (a current Fluent id) via the legacy catalog and transforms, filling
+        /// in any placeables from `args`. Returns `None` if there's no legacy catalog, no
+        /// mapping for `id`, or the mapped legacy key is missing.
+        pub fn lookup(&self, id: &str, args: &[(&str, Cow<str>)]) -> Option<String> {
+            let mapping = LEGACY_MAPPINGS
+                .iter()
+                .find(|(mapped_id, _)| *mapped_id == id)
+                .map(|(_, mapping)| mapping)?;
+            let mut value = self.values.get(mapping.legacy_key)?.clone();
+
+            for transform in mapping.transforms {
+                value = match transform {
+                    Transform::SplitParagraph { segment } => value
+                        .split("\n\n")
+                        .nth(*segment)
+                        .unwrap_or(&value)
+                        .to_owned(),
+                    Transform::Replace { from, to } => value.replace(from, to),
+                };
+            }
+
+            // Now substitute the `{ $var }` placeables we just introduced with the
+            // caller-supplied argument values (a minimal stand-in for full Fluent
+            // resolution, since the legacy value is otherwise plain text).
+            for (key, arg_value) in args {
+                value = value.replace(&format!("{{ ${key} }}"), arg_value);
+            }
+
+            Some(value)
+        }
+    }
+
+    /// Load the legacy `crashreporter.ini`-style key/value catalog, if present.
+    pub fn load() -> LegacyStrings {
+        let Some(path) = crate::config::installation_resource_path()
+            .join("crashreporter.ini")
+            .canonicalize()
+            .ok()
+        else {
+            return LegacyStrings { values: HashMap::new() };
+        };
+
+        let values = std::fs::read_to_string(&path)
+            .ok()
+            .map(|contents| parse_ini_strings(&contents))
+            .unwrap_or_default();
+
+        LegacyStrings { values }
+    }
+
+    /// Parse a minimal `key=value` `.ini`-style file, skipping section headers and comments.
+    fn parse_ini_strings(contents: &str) -> HashMap<String, String> {
+        contents
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+                    return None;
+                }
+                let (key, value) = line.split_once('=')?;
+                Some((key.trim().to_owned(), value.trim().to_owned()))
+            })
+            .collect()
+    }
+}
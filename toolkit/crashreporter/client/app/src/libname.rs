@@ -0,0 +1,18 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Platform naming conventions for shared libraries shipped in the installation, so callers
+//! don't need to duplicate a `cfg!` ladder every time they want to locate one.
+
+/// The file name of the shared library whose base name is `stem` (e.g. `XUL`) on the current
+/// platform: `XUL` on macOS, `xul.dll` on Windows, `libxul.so` elsewhere.
+pub fn shared_library_name(stem: &str) -> String {
+    if cfg!(target_os = "macos") {
+        stem.to_owned()
+    } else if cfg!(target_os = "windows") {
+        format!("{}.dll", stem.to_lowercase())
+    } else {
+        format!("lib{}.so", stem.to_lowercase())
+    }
+}
@@ -0,0 +1,113 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! A small process-launching subsystem for relaunching Firefox after a crash report is
+//! submitted, modeled on mozrunner's `Runner`/`RunnerProcess` split: a builder that's
+//! configured up front, and a handle to the running process once it's started.
+
+use crate::std::ffi::OsStr;
+use crate::std::process::{Child, Command, ExitStatus, Stdio};
+use crate::std;
+
+/// Builds up a Firefox invocation before starting it.
+pub struct Runner {
+    command: Command,
+}
+
+impl Runner {
+    /// Start building a launch of `program`.
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Self {
+        Runner {
+            command: Command::new(program),
+        }
+    }
+
+    /// Add a single argument.
+    pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
+        self.command.arg(arg);
+        self
+    }
+
+    /// Add several arguments at once.
+    pub fn args<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(mut self, args: I) -> Self {
+        self.command.args(args);
+        self
+    }
+
+    /// Set a single environment variable.
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(mut self, key: K, value: V) -> Self {
+        self.command.env(key, value);
+        self
+    }
+
+    /// Set several environment variables at once.
+    pub fn envs<I: IntoIterator<Item = (K, V)>, K: AsRef<OsStr>, V: AsRef<OsStr>>(
+        mut self,
+        vars: I,
+    ) -> Self {
+        self.command.envs(vars);
+        self
+    }
+
+    /// Redirect the child's stdout.
+    pub fn stdout(mut self, cfg: Stdio) -> Self {
+        self.command.stdout(cfg);
+        self
+    }
+
+    /// Redirect the child's stderr.
+    pub fn stderr(mut self, cfg: Stdio) -> Self {
+        self.command.stderr(cfg);
+        self
+    }
+
+    /// Start the process, returning a handle to it.
+    pub fn start(mut self) -> std::io::Result<RunnerProcess> {
+        self.command.stdin(Stdio::null());
+        log::debug!("starting process: {:?}", self.command);
+        Ok(RunnerProcess {
+            child: self.command.spawn()?,
+        })
+    }
+}
+
+/// A handle to a process started by a [`Runner`].
+pub struct RunnerProcess {
+    child: Child,
+}
+
+impl RunnerProcess {
+    /// Check whether the process has exited, without blocking.
+    pub fn try_wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        self.child.try_wait()
+    }
+
+    /// Whether the process is still running.
+    pub fn running(&mut self) -> bool {
+        matches!(self.try_wait(), Ok(None))
+    }
+
+    /// Forcibly terminate the process.
+    pub fn kill(&mut self) -> std::io::Result<()> {
+        self.child.kill()
+    }
+}
+
+/// Build a [`Runner`] that relaunches `program` with the original command-line arguments and,
+/// if given, `XUL_APP_FILE`, discarding its stdout and stderr. No profile argument is added;
+/// the profile is whatever the passed arguments select.
+pub fn firefox_relauncher(
+    program: &OsStr,
+    args: &[crate::std::ffi::OsString],
+    app_file: Option<&OsStr>,
+) -> Runner {
+    let mut runner = Runner::new(program)
+        .args(args.iter())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(app_file) = app_file {
+        runner = runner.env("XUL_APP_FILE", app_file);
+    }
+    runner
+}
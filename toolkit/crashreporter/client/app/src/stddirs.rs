@@ -0,0 +1,28 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Fallback directory discovery using the `dirs` crate's platform conventions, for when the
+//! reporter is run standalone (e.g. double-clicked) rather than spawned by Firefox with its
+//! `MOZ_CRASHREPORTER_*` environment fully populated.
+
+use crate::std::path::PathBuf;
+
+/// The directory holding pending crash reports for `vendor`/`product`, under the platform's
+/// standard per-user data directory (`%APPDATA%`/`~/Library/Application Support`/
+/// `$XDG_DATA_HOME`). Returns `None` if the platform data directory can't be determined.
+pub fn pending_crashes_dir(vendor: &str, product: &str) -> Option<PathBuf> {
+    Some(
+        dirs::data_dir()?
+            .join(vendor)
+            .join(product)
+            .join("Crash Reports")
+            .join("pending"),
+    )
+}
+
+/// The default profile root for `vendor`/`product`, under the platform's standard per-user
+/// data directory. Returns `None` if the platform data directory can't be determined.
+pub fn profile_root(vendor: &str, product: &str) -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join(vendor).join(product))
+}